@@ -0,0 +1,361 @@
+use bevy::{prelude::*, utils::HashSet};
+use serde::{Deserialize, Serialize};
+
+use super::model::Model;
+use crate::line_material::LineMaterial;
+
+/// Keyframes are added at this rate when a curve has to be resampled for an `AnimationClip`
+const SAMPLES_PER_SECOND: f32 = 30.;
+
+/// Rotations are split into turns of at most this many degrees, since an `AnimationClip` always
+/// turns the short way between two keyframes
+const MAX_TURN_DEGREES: f32 = 90.;
+
+/// A named animation declared in a `.mdl.ron` file. The loader turns its transform tracks into an
+/// `AnimationClip`, labeled `animations/<name>`; color tracks are applied by
+/// [`animate_model_colors`].
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ModelAnimation {
+    #[serde(default)]
+    pub repeat: bool,
+    pub tracks: Vec<Track>,
+    #[serde(skip)]
+    pub clip: Handle<AnimationClip>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub enum Track {
+    /// Euler angles in degrees, applied in XYZ order, so a full turn can be written as 0 to 360
+    Rotation(Keyframes<Vec3>),
+    Translation(Keyframes<Vec3>),
+    Scale(Keyframes<Vec3>),
+    /// Multiplies the model's own colors
+    Color(Keyframes<Color>),
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Keyframes<T> {
+    #[serde(default)]
+    pub interpolation: Interpolation,
+    /// Pairs of a time in seconds and the value at that time, in order
+    pub keyframes: Vec<(f32, T)>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+pub enum Interpolation {
+    #[default]
+    Linear,
+    /// Holds each value until the next keyframe
+    Step,
+    /// Eases in and out of every keyframe
+    Smooth,
+}
+
+pub trait Interpolate: Copy {
+    fn interpolate(self, other: Self, t: f32) -> Self;
+}
+
+impl Interpolate for Vec3 {
+    fn interpolate(self, other: Self, t: f32) -> Self {
+        self.lerp(other, t)
+    }
+}
+
+impl Interpolate for Color {
+    fn interpolate(self, other: Self, t: f32) -> Self {
+        let [r, g, b, a] = Vec4::from(self.as_rgba_f32())
+            .lerp(Vec4::from(other.as_rgba_f32()), t)
+            .to_array();
+
+        Color::rgba(r, g, b, a)
+    }
+}
+
+impl<T: Interpolate> Keyframes<T> {
+    pub fn duration(&self) -> f32 {
+        self.keyframes.last().map_or(0., |(time, _)| *time)
+    }
+
+    /// The value at `time`, holding the first and last values outside of the keyframes
+    pub fn sample(&self, time: f32) -> Option<T> {
+        let next = self.keyframes.iter().position(|(t, _)| *t > time);
+
+        match next {
+            None => self.keyframes.last().map(|(_, value)| *value),
+            Some(0) => Some(self.keyframes[0].1),
+            Some(next) => {
+                let (start, from) = self.keyframes[next - 1];
+                let (end, to) = self.keyframes[next];
+                let t = (time - start) / (end - start);
+
+                Some(match self.interpolation {
+                    Interpolation::Linear => from.interpolate(to, t),
+                    Interpolation::Step => from,
+                    Interpolation::Smooth => from.interpolate(to, t * t * (3. - 2. * t)),
+                })
+            }
+        }
+    }
+
+    /// Keyframes that reproduce this curve when interpolated linearly, like `AnimationClip` does
+    fn linear_keyframes(&self) -> (Vec<f32>, Vec<T>) {
+        match self.interpolation {
+            Interpolation::Linear => self.keyframes.iter().copied().unzip(),
+            Interpolation::Step => self
+                .keyframes
+                .iter()
+                .zip(self.keyframes.iter().skip(1))
+                .flat_map(|(&(start, value), &(end, _))| {
+                    [(start, value), ((end - 0.001).max(start), value)]
+                })
+                .chain(self.keyframes.last().copied())
+                .unzip(),
+            Interpolation::Smooth => {
+                let samples = (self.duration() * SAMPLES_PER_SECOND).ceil() as usize;
+
+                (0..=samples)
+                    .map(|i| i as f32 / SAMPLES_PER_SECOND)
+                    .filter_map(|time| Some((time, self.sample(time)?)))
+                    .unzip()
+            }
+        }
+    }
+}
+
+impl ModelAnimation {
+    pub fn duration(&self) -> f32 {
+        self.tracks
+            .iter()
+            .map(|track| match track {
+                Track::Rotation(keyframes)
+                | Track::Translation(keyframes)
+                | Track::Scale(keyframes) => keyframes.duration(),
+                Track::Color(keyframes) => keyframes.duration(),
+            })
+            .fold(0., f32::max)
+    }
+
+    pub fn color_track(&self) -> Option<&Keyframes<Color>> {
+        self.tracks.iter().find_map(|track| match track {
+            Track::Color(keyframes) => Some(keyframes),
+            _ => None,
+        })
+    }
+
+    /// Builds a clip that animates the entity playing it
+    pub fn to_clip(&self) -> AnimationClip {
+        let mut clip = AnimationClip::default();
+
+        // The first part of a path names the entity with the `AnimationPlayer`, and is ignored
+        let path = EntityPath { parts: vec![Name::new("model")] };
+
+        for track in &self.tracks {
+            let curve = match track {
+                Track::Rotation(keyframes) => {
+                    let (keyframe_timestamps, rotations) = rotation_keyframes(keyframes);
+
+                    VariableCurve {
+                        keyframe_timestamps,
+                        keyframes: bevy::animation::Keyframes::Rotation(rotations),
+                    }
+                }
+                Track::Translation(keyframes) => {
+                    let (keyframe_timestamps, translations) = keyframes.linear_keyframes();
+
+                    VariableCurve {
+                        keyframe_timestamps,
+                        keyframes: bevy::animation::Keyframes::Translation(translations),
+                    }
+                }
+                Track::Scale(keyframes) => {
+                    let (keyframe_timestamps, scales) = keyframes.linear_keyframes();
+
+                    VariableCurve {
+                        keyframe_timestamps,
+                        keyframes: bevy::animation::Keyframes::Scale(scales),
+                    }
+                }
+                Track::Color(_) => continue,
+            };
+
+            clip.add_curve_to_path(path.clone(), curve);
+        }
+
+        clip
+    }
+}
+
+/// Turns Euler angles into rotations, with keyframes added in between wherever the angles change
+/// by more than [`MAX_TURN_DEGREES`], so turns of 180 degrees or more go the whole way around
+fn rotation_keyframes(keyframes: &Keyframes<Vec3>) -> (Vec<f32>, Vec<Quat>) {
+    let (times, angles) = keyframes.linear_keyframes();
+
+    let mut split = vec![];
+    let mut previous: Option<(f32, Vec3)> = None;
+    for (time, angle) in times.into_iter().zip(angles) {
+        if let Some((start, from)) = previous {
+            // The axes add up, so no step turns further than the limit altogether
+            let turn = (angle - from).abs().dot(Vec3::ONE);
+            let steps = (turn / MAX_TURN_DEGREES).ceil().max(1.) as usize;
+
+            for step in 1..steps {
+                let t = step as f32 / steps as f32;
+                split.push((start + (time - start) * t, from.lerp(angle, t)));
+            }
+        }
+
+        split.push((time, angle));
+        previous = Some((time, angle));
+    }
+
+    split
+        .into_iter()
+        .map(|(time, angles)| {
+            let [x, y, z] = angles.to_array().map(f32::to_radians);
+            (time, Quat::from_euler(EulerRot::XYZ, x, y, z))
+        })
+        .unzip()
+}
+
+/// Plays one of a model's animations on this entity once the model has loaded, and again whenever
+/// it is reloaded
+#[derive(Component)]
+pub struct PlayModelAnimation {
+    pub model: Handle<Model>,
+    pub animation: String,
+}
+
+pub fn start_model_animations(
+    mut events: EventReader<AssetEvent<Model>>,
+    models: Res<Assets<Model>>,
+    mut materials: ResMut<Assets<LineMaterial>>,
+    mut query: Query<(
+        Entity,
+        Ref<PlayModelAnimation>,
+        Option<&mut AnimationPlayer>,
+        Option<&Handle<LineMaterial>>,
+    )>,
+    mut commands: Commands,
+) {
+    let loaded: HashSet<_> = events
+        .iter()
+        .filter_map(|event| match event {
+            AssetEvent::Created { handle } | AssetEvent::Modified { handle } => Some(handle.id()),
+            AssetEvent::Removed { .. } => None,
+        })
+        .collect();
+
+    for (entity, request, animation_player, material) in query.iter_mut() {
+        if !request.is_added() && !loaded.contains(&request.model.id()) {
+            continue;
+        }
+
+        let Some(model) = models.get(&request.model) else {
+            continue;
+        };
+
+        let Some(animation) = model.animations.get(&request.animation) else {
+            warn!("Model has no animation named {:?}", request.animation);
+            continue;
+        };
+
+        match animation_player {
+            Some(mut animation_player) => play(&mut animation_player, animation),
+            None => {
+                let mut animation_player = AnimationPlayer::default();
+                play(&mut animation_player, animation);
+                commands.entity(entity).insert(animation_player);
+            }
+        }
+
+        // Color tracks need a material of their own, so other users of the model are unaffected
+        if animation.color_track().is_some() {
+            let material = material
+                .and_then(|material| materials.get(material))
+                .cloned()
+                .unwrap_or_default();
+
+            commands.entity(entity).insert(materials.add(material));
+        }
+    }
+}
+
+fn play(animation_player: &mut AnimationPlayer, animation: &ModelAnimation) {
+    animation_player.start(animation.clip.clone());
+
+    if animation.repeat {
+        animation_player.repeat();
+    } else {
+        animation_player.stop_repeating();
+    }
+}
+
+pub fn animate_model_colors(
+    models: Res<Assets<Model>>,
+    mut materials: ResMut<Assets<LineMaterial>>,
+    query: Query<(&PlayModelAnimation, &AnimationPlayer, &Handle<LineMaterial>)>,
+) {
+    for (request, animation_player, material) in query.iter() {
+        let Some(animation) = models
+            .get(&request.model)
+            .and_then(|model| model.animations.get(&request.animation))
+        else {
+            continue;
+        };
+
+        let Some(color_track) = animation.color_track() else {
+            continue;
+        };
+
+        let duration = animation.duration();
+        let mut time = animation_player.elapsed();
+        if animation.repeat && duration > 0. {
+            time %= duration;
+        }
+
+        if let (Some(color), Some(material)) =
+            (color_track.sample(time), materials.get_mut(material))
+        {
+            // The shader multiplies the mesh's colors by this, so white leaves them as they are
+            material.color = color;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::PI;
+
+    use super::{Keyframes, *};
+
+    /// Samples rotation keyframes the way an `AnimationClip` does
+    fn sample_rotation(times: &[f32], rotations: &[Quat], time: f32) -> Quat {
+        let next = times.iter().position(|t| *t > time).unwrap();
+        let (start, end) = (times[next - 1], times[next]);
+        let t = (time - start) / (end - start);
+
+        let (from, mut to) = (rotations[next - 1], rotations[next]);
+        if to.dot(from) < 0. {
+            to = -to;
+        }
+
+        from.slerp(to, t)
+    }
+
+    #[test]
+    fn full_turn_goes_around() {
+        let keyframes = Keyframes {
+            interpolation: Interpolation::Linear,
+            keyframes: vec![(0., Vec3::ZERO), (1., Vec3::new(0., 0., 360.))],
+        };
+
+        let (times, rotations) = rotation_keyframes(&keyframes);
+
+        // Halfway through, a full turn is half a turn in
+        let halfway = sample_rotation(&times, &rotations, 0.5);
+        assert!(halfway.angle_between(Quat::from_rotation_z(PI)) < 1e-3);
+
+        let quarter = sample_rotation(&times, &rotations, 0.25);
+        assert!(quarter.angle_between(Quat::from_rotation_z(PI / 2.)) < 1e-3);
+    }
+}
@@ -0,0 +1,376 @@
+use std::{collections::BTreeMap, f32::consts::TAU};
+
+use bevy::{
+    asset::{AssetLoader, LoadContext, LoadedAsset},
+    prelude::*,
+    utils::{BoxedFuture, HashMap},
+};
+use thiserror::Error;
+
+use super::map::{Map, MapMesh};
+use crate::{line_material::ColorSpec, surface::Surface, utils::drawing};
+
+/// Settings for turning the entities of an ASCII DXF drawing into walls. Only the top view is
+/// read: extrusion directions are ignored, so everything is assumed to be drawn on the XY plane.
+pub struct DxfImport {
+    /// World units per drawing unit
+    pub scale: f32,
+    /// How many lines a full circle is drawn with; arcs get their share of these
+    pub circle_segments: usize,
+}
+
+impl Default for DxfImport {
+    fn default() -> Self {
+        Self { scale: 1., circle_segments: 32 }
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum DxfError {
+    #[error("only ASCII DXF files are supported")]
+    Binary,
+    #[error("DXF files must be UTF-8")]
+    NotUtf8(#[from] std::str::Utf8Error),
+    #[error("line {line}: expected a group code, found {found:?}")]
+    GroupCode { line: usize, found: String },
+    #[error("line {line}: group code {code} is missing its value")]
+    MissingValue { line: usize, code: i32 },
+    #[error("line {line}: group {code} of {entity} should be a number, found {found:?}")]
+    Number {
+        line: usize,
+        code: i32,
+        entity: String,
+        found: String,
+    },
+}
+
+/// A group code and its value, with the line the code was on
+struct Group<'a> {
+    line: usize,
+    code: i32,
+    value: &'a str,
+}
+
+/// The groups from one `0` group to the next, such as an entity or a table entry
+struct Record<'a> {
+    kind: &'a str,
+    groups: Vec<Group<'a>>,
+}
+
+impl Record<'_> {
+    fn value(&self, code: i32) -> Option<&str> {
+        self.groups
+            .iter()
+            .find(|group| group.code == code)
+            .map(|group| group.value)
+    }
+
+    fn parse<T: std::str::FromStr>(&self, group: &Group) -> Result<T, DxfError> {
+        group.value.parse().map_err(|_| DxfError::Number {
+            line: group.line,
+            code: group.code,
+            entity: self.kind.to_owned(),
+            found: group.value.to_owned(),
+        })
+    }
+
+    /// A number, or its default of zero if the group is left out
+    fn number(&self, code: i32) -> Result<f32, DxfError> {
+        match self.groups.iter().find(|group| group.code == code) {
+            Some(group) => self.parse(group),
+            None => Ok(0.),
+        }
+    }
+
+    fn integer(&self, code: i32) -> Result<Option<i32>, DxfError> {
+        self.groups
+            .iter()
+            .find(|group| group.code == code)
+            .map(|group| self.parse(group))
+            .transpose()
+    }
+
+    fn point(&self, x: i32) -> Result<Vec3, DxfError> {
+        Ok(Vec3::new(self.number(x)?, self.number(x + 10)?, self.number(x + 20)?))
+    }
+
+    fn layer(&self) -> &str {
+        self.value(8).unwrap_or("0")
+    }
+
+    /// The entity's own color, if it doesn't use its layer's
+    fn color(&self) -> Result<Option<Color>, DxfError> {
+        if let Some(true_color) = self.integer(420)? {
+            let [_, r, g, b] = true_color.to_be_bytes();
+            return Ok(Some(Color::rgb_u8(r, g, b)));
+        }
+
+        // 0 is "by block" and 256 is "by layer", and blocks aren't supported
+        Ok(match self.integer(62)? {
+            Some(index @ 1..=255) => Some(aci_color(index as u8)),
+            _ => None,
+        })
+    }
+}
+
+fn records(text: &str) -> Result<Vec<Record>, DxfError> {
+    let mut lines = text.lines().enumerate();
+    let mut records: Vec<Record> = vec![];
+
+    while let Some((number, code)) = lines.next() {
+        let code = code.trim();
+        if code.is_empty() {
+            continue;
+        }
+
+        let line = number + 1;
+        let code: i32 = code
+            .parse()
+            .map_err(|_| DxfError::GroupCode { line, found: code.to_owned() })?;
+
+        let (_, value) = lines.next().ok_or(DxfError::MissingValue { line, code })?;
+        let value = value.trim();
+
+        if code == 0 {
+            records.push(Record { kind: value, groups: vec![] });
+        } else if let Some(record) = records.last_mut() {
+            record.groups.push(Group { line, code, value });
+        }
+    }
+
+    Ok(records)
+}
+
+impl DxfImport {
+    /// One `MapMesh` for each layer that has lines on it, in the color of the layer. Entities
+    /// with colors of their own keep them. Layers that are turned off are left out.
+    pub fn map_meshes(&self, bytes: &[u8]) -> Result<Vec<MapMesh>, DxfError> {
+        if bytes.starts_with(b"AutoCAD Binary DXF") {
+            return Err(DxfError::Binary);
+        }
+
+        let records = records(std::str::from_utf8(bytes)?)?;
+
+        let mut layer_colors = HashMap::new();
+        let mut hidden_layers = vec![];
+        let mut layers: BTreeMap<&str, (Vec<(Vec3, Vec3)>, Vec<Option<Color>>)> = default();
+        let mut section = None;
+
+        for record in &records {
+            match record.kind {
+                "SECTION" => section = record.value(2),
+                "ENDSEC" => section = None,
+                "LAYER" if section == Some("TABLES") => {
+                    let name = record.value(2).unwrap_or("0");
+                    let index = record.integer(62)?.unwrap_or(7);
+
+                    // A negative color index means the layer is off
+                    if index < 0 {
+                        hidden_layers.push(name);
+                    }
+                    layer_colors.insert(name, aci_color(index.unsigned_abs().min(255) as u8));
+                }
+                kind if section == Some("ENTITIES") => {
+                    let lines = match kind {
+                        "LINE" => vec![(record.point(10)?, record.point(11)?)],
+                        "LWPOLYLINE" => self.polyline(record)?,
+                        "ARC" => {
+                            let center = record.point(10)?;
+                            let start = record.number(50)?.to_radians();
+                            let mut end = record.number(51)?.to_radians();
+                            // Arcs always run counterclockwise
+                            if end <= start {
+                                end += TAU;
+                            }
+
+                            drawing::arc(
+                                record.number(40)?,
+                                start,
+                                end,
+                                self.segments(end - start),
+                                false,
+                            )
+                            .into_iter()
+                            .map(|(a, b)| (center + a, center + b))
+                            .collect()
+                        }
+                        "CIRCLE" => {
+                            let center = record.point(10)?;
+
+                            drawing::circle(record.number(40)?, self.circle_segments)
+                                .into_iter()
+                                .map(|(a, b)| (center + a, center + b))
+                                .collect()
+                        }
+                        _ => continue,
+                    };
+
+                    let color = record.color()?;
+                    let (layer_lines, colors) = layers.entry(record.layer()).or_default();
+
+                    colors.extend(std::iter::repeat(color).take(lines.len() * 2));
+                    layer_lines.extend(
+                        lines
+                            .into_iter()
+                            .map(|(a, b)| (a * self.scale, b * self.scale)),
+                    );
+                }
+                _ => {}
+            }
+        }
+
+        let map_meshes = layers
+            .into_iter()
+            .filter(|(name, (lines, _))| !hidden_layers.contains(name) && !lines.is_empty())
+            .map(|(name, (lines, colors))| {
+                let layer_color = layer_colors.get(name).copied().unwrap_or(Color::WHITE);
+                let colors: Vec<_> = colors
+                    .into_iter()
+                    .map(|color| color.unwrap_or(layer_color))
+                    .collect();
+
+                MapMesh {
+                    transform: Transform::default(),
+                    lines,
+                    primitives: vec![],
+                    includes: vec![],
+                    colors: ColorSpec::from_vertex_colors(&colors),
+                    intensity: 1.,
+                    surface: Surface::default(),
+                    source: None,
+                }
+            })
+            .collect();
+
+        Ok(map_meshes)
+    }
+
+    pub fn map(&self, bytes: &[u8]) -> Result<Map, DxfError> {
+        Ok(Map::new(self.map_meshes(bytes)?, vec![]))
+    }
+
+    /// The lines of a lightweight polyline, with its bulges drawn as arcs
+    fn polyline(&self, record: &Record) -> Result<Vec<(Vec3, Vec3)>, DxfError> {
+        let elevation = record.number(38)?;
+        let closed = record.integer(70)?.unwrap_or(0) & 1 != 0;
+
+        // Each vertex starts with its X, and may be followed by a bulge for the segment after it
+        let mut vertices: Vec<(Vec2, f32)> = vec![];
+        for group in &record.groups {
+            match (group.code, vertices.last_mut()) {
+                (10, _) => vertices.push((Vec2::new(record.parse(group)?, 0.), 0.)),
+                (20, Some((vertex, _))) => vertex.y = record.parse(group)?,
+                (42, Some((_, bulge))) => *bulge = record.parse(group)?,
+                _ => {}
+            }
+        }
+
+        let segment_count = match closed {
+            true => vertices.len(),
+            false => vertices.len().saturating_sub(1),
+        };
+
+        let mut points = vec![];
+        for i in 0..segment_count {
+            let (from, bulge) = vertices[i];
+            let (to, _) = vertices[(i + 1) % vertices.len()];
+
+            if bulge.abs() < f32::EPSILON {
+                points.push((from, to));
+                continue;
+            }
+
+            // The bulge is the tangent of a quarter of the arc's angle, negative if it runs
+            // clockwise
+            let angle = 4. * bulge.atan();
+            let chord = to - from;
+            let center = (from + to) / 2. + chord.perp() * ((1. - bulge * bulge) / (4. * bulge));
+            let radius = from.distance(center);
+            let start = (from - center).y.atan2((from - center).x);
+
+            let segments = self.segments(angle.abs());
+            let mut previous = from;
+            for step in 1..=segments {
+                let next = match step {
+                    _ if step == segments => to,
+                    _ => {
+                        let t = start + angle * step as f32 / segments as f32;
+                        center + Vec2::new(t.cos(), t.sin()) * radius
+                    }
+                };
+
+                points.push((previous, next));
+                previous = next;
+            }
+        }
+
+        Ok(points
+            .into_iter()
+            .map(|(a, b)| (a.extend(elevation), b.extend(elevation)))
+            .collect())
+    }
+
+    /// How many lines an arc of `angle` radians is drawn with
+    fn segments(&self, angle: f32) -> usize {
+        ((angle / TAU * self.circle_segments as f32).ceil() as usize).max(1)
+    }
+}
+
+/// Looks up an AutoCAD Color Index. Apart from the first few named colors and the grays at the
+/// end, the index runs through 24 hues, each in five shades of a full and a pale version.
+pub fn aci_color(index: u8) -> Color {
+    match index {
+        1 => Color::rgb_u8(255, 0, 0),
+        2 => Color::rgb_u8(255, 255, 0),
+        3 => Color::rgb_u8(0, 255, 0),
+        4 => Color::rgb_u8(0, 255, 255),
+        5 => Color::rgb_u8(0, 0, 255),
+        6 => Color::rgb_u8(255, 0, 255),
+        8 => Color::rgb_u8(128, 128, 128),
+        9 => Color::rgb_u8(192, 192, 192),
+        10..=249 => {
+            let index = index - 10;
+            let hue = (index / 10) as f32 * 15.;
+            let value = [1., 0.65, 0.5, 0.3, 0.15][(index % 10 / 2) as usize];
+            let saturation = if index % 2 == 0 { 1. } else { 0.5 };
+
+            hsv(hue, saturation, value)
+        }
+        250..=255 => {
+            let gray = [51, 91, 132, 173, 214, 255][(index - 250) as usize];
+            Color::rgb_u8(gray, gray, gray)
+        }
+        // 7 is black or white depending on the background, which is always black here
+        _ => Color::WHITE,
+    }
+}
+
+fn hsv(hue: f32, saturation: f32, value: f32) -> Color {
+    let channel = |n: f32| {
+        let k = (n + hue / 60.) % 6.;
+        value - value * saturation * k.min(4. - k).clamp(0., 1.)
+    };
+
+    Color::rgb(channel(5.), channel(3.), channel(1.))
+}
+
+/// Loads `.dxf` drawings as maps, using the default [`DxfImport`] settings
+pub struct DxfLoader;
+impl AssetLoader for DxfLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+        Box::pin(async move {
+            let map = DxfImport::default().map(bytes)?;
+            load_context.set_default_asset(LoadedAsset::new(map));
+
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["dxf"]
+    }
+}
@@ -0,0 +1,344 @@
+//! Builds maps out of randomly placed rooms and arenas, joined by corridors.
+//!
+//! The level is laid out on a grid of tiles that are either open or solid, and every edge between
+//! the two becomes a wall. Corridors are one tile wide.
+
+use std::collections::BTreeMap;
+
+use bevy::prelude::*;
+use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+
+use super::{Map, MapMesh, Placement, PlacementKind};
+use crate::{
+    assets::primitive::Primitive, enemy::EnemyKind, line_material::ColorSpec, pickup::PickupKind,
+    surface::Surface,
+};
+
+/// Settings for [`MapGenerator::generate`]. The same settings always give the same map.
+#[derive(Resource, Reflect, Clone)]
+#[reflect(Resource)]
+pub struct MapGenerator {
+    pub seed: u64,
+    /// Size of the map, in tiles
+    pub width: u32,
+    pub height: u32,
+    /// World units per tile
+    pub tile_size: f32,
+    /// Walls are split into a mesh for every square this many tiles wide, so they can be
+    /// streamed in and edited a piece at a time
+    pub mesh_size: u32,
+    pub rooms: u32,
+    /// The shortest and longest a side of a room can be, in tiles
+    pub min_room_size: u32,
+    pub max_room_size: u32,
+    /// Big square rooms with pillars to fight around
+    pub arenas: u32,
+    pub arena_size: u32,
+    pub pillars_per_arena: u32,
+    pub enemies_per_room: u32,
+    pub enemies_per_arena: u32,
+    /// The chance of a room having a health pickup
+    pub pickup_chance: f32,
+    pub wall_color: Color,
+    pub pillar_color: Color,
+    pub intensity: f32,
+}
+
+impl Default for MapGenerator {
+    fn default() -> Self {
+        Self {
+            seed: 0,
+            width: 64,
+            height: 64,
+            tile_size: 4.,
+            mesh_size: 8,
+            rooms: 8,
+            min_room_size: 4,
+            max_room_size: 10,
+            arenas: 2,
+            arena_size: 16,
+            pillars_per_arena: 4,
+            enemies_per_room: 1,
+            enemies_per_arena: 5,
+            pickup_chance: 0.3,
+            wall_color: Color::rgb(0., 0.5, 1.),
+            pillar_color: Color::rgb(1., 0.2, 0.6),
+            intensity: 2.,
+        }
+    }
+}
+
+/// A rectangle of open tiles
+struct Room {
+    min: IVec2,
+    size: IVec2,
+    arena: bool,
+}
+
+impl Room {
+    fn center(&self) -> IVec2 {
+        self.min + self.size / 2
+    }
+
+    /// Whether the rooms are closer than `margin` tiles
+    fn overlaps(&self, other: &Room, margin: i32) -> bool {
+        let max = self.min + self.size;
+        let other_max = other.min + other.size;
+
+        self.min.x < other_max.x + margin
+            && other.min.x < max.x + margin
+            && self.min.y < other_max.y + margin
+            && other.min.y < max.y + margin
+    }
+
+    fn random_tile(&self, rng: &mut StdRng) -> IVec2 {
+        let max = self.min + self.size;
+        IVec2::new(rng.gen_range(self.min.x..max.x), rng.gen_range(self.min.y..max.y))
+    }
+}
+
+/// Which tiles are open
+struct Grid {
+    size: IVec2,
+    open: Vec<bool>,
+}
+
+impl Grid {
+    fn is_open(&self, tile: IVec2) -> bool {
+        tile.cmpge(IVec2::ZERO).all()
+            && tile.cmplt(self.size).all()
+            && self.open[(tile.y * self.size.x + tile.x) as usize]
+    }
+
+    fn carve(&mut self, min: IVec2, max: IVec2) {
+        let (min, max) = (min.min(max).max(IVec2::ZERO), min.max(max).min(self.size - 1));
+
+        for y in min.y..=max.y {
+            for x in min.x..=max.x {
+                self.open[(y * self.size.x + x) as usize] = true;
+            }
+        }
+    }
+
+    /// The edges between open and solid tiles, with edges in a row joined into one line. Lines
+    /// are cut every `split` tiles, so each one falls in a single mesh.
+    fn walls(&self, split: i32) -> Vec<(IVec2, IVec2)> {
+        let mut walls = vec![];
+
+        // Horizontal edges first, then vertical ones by swapping the axes
+        for (axis, across) in [(IVec2::X, IVec2::Y), (IVec2::Y, IVec2::X)] {
+            let length = self.size.dot(axis);
+            let rows = self.size.dot(across);
+
+            for row in 0..=rows {
+                let mut start = None;
+
+                for step in 0..=length {
+                    let tile = axis * step + across * row;
+                    let edge = step < length && self.is_open(tile) != self.is_open(tile - across);
+
+                    if let Some(from) = start.filter(|_| !edge || step % split == 0) {
+                        walls.push((from, tile));
+                        start = None;
+                    }
+
+                    if edge && start.is_none() {
+                        start = Some(tile);
+                    }
+                }
+            }
+        }
+
+        walls
+    }
+}
+
+impl MapGenerator {
+    pub fn generate(&self) -> Map {
+        let mut rng = StdRng::seed_from_u64(self.seed);
+        let size = IVec2::new(self.width as i32, self.height as i32);
+
+        let min_room_size = self.min_room_size.max(1) as i32;
+        let max_room_size = (self.max_room_size as i32).max(min_room_size);
+
+        // Arenas are placed first, while there is still space for them
+        let mut rooms: Vec<Room> = vec![];
+        for (arena, count) in [(true, self.arenas), (false, self.rooms)] {
+            for _ in 0..count {
+                for _ in 0..100 {
+                    let room_size = match arena {
+                        true => IVec2::splat(self.arena_size.max(1) as i32),
+                        false => IVec2::new(
+                            rng.gen_range(min_room_size..=max_room_size),
+                            rng.gen_range(min_room_size..=max_room_size),
+                        ),
+                    };
+
+                    // Rooms stay a tile away from the edge so they always have walls
+                    let max = size - room_size - 1;
+                    if max.x < 1 || max.y < 1 {
+                        break;
+                    }
+
+                    let room = Room {
+                        min: IVec2::new(rng.gen_range(1..=max.x), rng.gen_range(1..=max.y)),
+                        size: room_size,
+                        arena,
+                    };
+
+                    if rooms.iter().all(|other| !room.overlaps(other, 2)) {
+                        rooms.push(room);
+                        break;
+                    }
+                }
+            }
+        }
+
+        let mut grid = Grid {
+            size,
+            open: vec![false; (size.x * size.y).max(0) as usize],
+        };
+
+        for room in &rooms {
+            grid.carve(room.min, room.min + room.size - 1);
+        }
+
+        // Join every room to the closest one before it, which connects them all
+        for (i, room) in rooms.iter().enumerate().skip(1) {
+            let Some(other) = rooms[..i].iter().min_by_key(|other| {
+                let distance = (other.center() - room.center()).abs();
+                distance.x + distance.y
+            }) else {
+                continue;
+            };
+
+            let (from, to) = (room.center(), other.center());
+            let corner = match rng.gen_bool(0.5) {
+                true => IVec2::new(to.x, from.y),
+                false => IVec2::new(from.x, to.y),
+            };
+
+            grid.carve(from, corner);
+            grid.carve(corner, to);
+        }
+
+        let origin = size.as_vec2() * self.tile_size / 2.;
+        let corner_position = |tile: IVec2| (tile.as_vec2() * self.tile_size - origin).extend(0.);
+        let tile_position =
+            |tile: IVec2| corner_position(tile) + Vec2::splat(self.tile_size / 2.).extend(0.);
+
+        let mesh_size = self.mesh_size.max(1) as i32;
+        let mesh_cell = |tile: IVec2| (tile.x / mesh_size, tile.y / mesh_size);
+
+        let mut walls: BTreeMap<_, Vec<_>> = BTreeMap::new();
+        for (from, to) in grid.walls(mesh_size) {
+            walls
+                .entry(mesh_cell(from))
+                .or_default()
+                .push((corner_position(from), corner_position(to)));
+        }
+
+        let mut pillars: BTreeMap<_, Vec<_>> = BTreeMap::new();
+        let mut placements = vec![];
+        let mut place = |kind, tile| {
+            placements.push(Placement {
+                transform: Transform::from_translation(tile_position(tile)),
+                kind,
+                properties: default(),
+            })
+        };
+
+        // The player starts in the first ordinary room, where there are no enemies
+        let start = rooms.iter().position(|room| !room.arena).unwrap_or(0);
+
+        for (i, room) in rooms.iter().enumerate() {
+            place(PlacementKind::Marker(format!("room {i}")), room.center());
+
+            let mut taken = vec![room.center()];
+
+            if room.arena {
+                // Corridors come in along the middle row and column, so pillars keep clear of them
+                let center = room.center();
+                let mut spots: Vec<_> = (room.min.y + 2..room.min.y + room.size.y - 2)
+                    .step_by(2)
+                    .flat_map(|y| {
+                        (room.min.x + 2..room.min.x + room.size.x - 2)
+                            .step_by(2)
+                            .map(move |x| IVec2::new(x, y))
+                    })
+                    .filter(|tile| (tile.x - center.x).abs() > 1 && (tile.y - center.y).abs() > 1)
+                    .collect();
+                spots.shuffle(&mut rng);
+
+                for tile in spots.into_iter().take(self.pillars_per_arena as usize) {
+                    pillars
+                        .entry(mesh_cell(tile))
+                        .or_default()
+                        .push(Primitive::Polygon {
+                            center: tile_position(tile),
+                            radius: self.tile_size / 2.,
+                            sides: rng.gen_range(3..=6),
+                            rotation: rng.gen_range(0. ..360.),
+                        });
+                    taken.push(tile);
+                }
+            }
+
+            let enemies = match room.arena {
+                _ if i == start => 0,
+                true => self.enemies_per_arena,
+                false => self.enemies_per_room,
+            };
+
+            let mut free_tile = |rng: &mut StdRng| {
+                let tile = (0..10)
+                    .map(|_| room.random_tile(rng))
+                    .find(|tile| !taken.contains(tile))?;
+                taken.push(tile);
+                Some(tile)
+            };
+
+            for _ in 0..enemies {
+                if let Some(tile) = free_tile(&mut rng) {
+                    place(PlacementKind::EnemySpawn(EnemyKind::Amoeba), tile);
+                }
+            }
+
+            if rng.gen_bool(self.pickup_chance.clamp(0., 1.) as f64) {
+                if let Some(tile) = free_tile(&mut rng) {
+                    place(PlacementKind::Pickup(PickupKind::Health), tile);
+                }
+            }
+        }
+
+        if let Some(start) = rooms.get(start) {
+            place(PlacementKind::PlayerStart, start.center());
+        }
+
+        let wall_meshes = walls.into_values().map(|lines| MapMesh {
+            transform: Transform::default(),
+            lines,
+            primitives: vec![],
+            includes: vec![],
+            colors: ColorSpec::Uniform(self.wall_color),
+            intensity: self.intensity,
+            surface: Surface::default(),
+            source: None,
+        });
+
+        let pillar_meshes = pillars.into_values().map(|primitives| MapMesh {
+            transform: Transform::default(),
+            lines: vec![],
+            primitives,
+            includes: vec![],
+            colors: ColorSpec::Uniform(self.pillar_color),
+            intensity: self.intensity,
+            surface: Surface::default(),
+            source: None,
+        });
+
+        let map_meshes = wall_meshes.chain(pillar_meshes).collect();
+
+        Map::new(map_meshes, placements)
+    }
+}
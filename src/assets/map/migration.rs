@@ -0,0 +1,303 @@
+//! Upgrades maps saved in older format versions.
+//!
+//! Each retired version keeps a frozen copy of its types in a `vN` module, together with an
+//! `upgrade` that turns it into version `N + 1`. [`migrate`] reads the version header and
+//! runs every step from there up to [`MAP_FORMAT_VERSION`]. This works the same for both
+//! [`MapEncoding`]s.
+
+use serde::Deserialize;
+
+use super::{Map, MapEncoding, MapError, MAP_FORMAT_VERSION};
+
+#[derive(Deserialize)]
+struct Header {
+    /// Maps written before the header existed have no version field at all
+    #[serde(default)]
+    version: u32,
+}
+
+pub fn migrate(bytes: &[u8]) -> Result<Map, MapError> {
+    let encoding = MapEncoding::detect(bytes);
+    let Header { version } = encoding.decode(bytes)?;
+
+    match version {
+        0 => Ok(encoding.decode::<v0::Map>(bytes)?.upgrade().upgrade()),
+        1 => Ok(encoding.decode::<v1::Map>(bytes)?.upgrade()),
+        MAP_FORMAT_VERSION => encoding.decode(bytes),
+        version => Err(MapError::UnsupportedVersion(version)),
+    }
+}
+
+/// Unversioned maps, identical to version 1 apart from the missing header
+mod v0 {
+    use bevy::prelude::*;
+    use serde::Deserialize;
+
+    use super::v1 as next;
+
+    #[derive(Deserialize)]
+    pub struct Map {
+        pub map_meshes: Vec<MapMesh>,
+    }
+
+    #[derive(Deserialize)]
+    pub struct MapMesh {
+        pub transform: Transform,
+        pub lines: next::LineList,
+    }
+
+    impl Map {
+        pub fn upgrade(self) -> next::Map {
+            next::Map {
+                map_meshes: self
+                    .map_meshes
+                    .into_iter()
+                    .map(|mesh| next::MapMesh {
+                        transform: mesh.transform,
+                        lines: mesh.lines,
+                        surface: Default::default(),
+                    })
+                    .collect(),
+                placements: vec![],
+            }
+        }
+    }
+}
+
+/// Walls with a single color that has the editor's intensity multiplied into it
+mod v1 {
+    use std::collections::BTreeMap;
+
+    use bevy::prelude::*;
+    use serde::Deserialize;
+
+    use crate::{
+        assets::map as next, enemy::EnemyKind as NextEnemyKind, line_material::ColorSpec,
+        pickup::PickupKind as NextPickupKind, surface::Surface as NextSurface,
+    };
+
+    #[derive(Deserialize)]
+    pub struct Map {
+        pub map_meshes: Vec<MapMesh>,
+        #[serde(default)]
+        pub placements: Vec<Placement>,
+    }
+
+    #[derive(Deserialize)]
+    pub struct MapMesh {
+        pub transform: Transform,
+        pub lines: LineList,
+        #[serde(default)]
+        pub surface: Surface,
+    }
+
+    #[derive(Deserialize)]
+    pub struct LineList {
+        pub lines: Vec<(Vec3, Vec3)>,
+        pub color: Color,
+    }
+
+    /// Walls had no shape or thickness yet, and were all polylines
+    #[derive(Deserialize)]
+    #[serde(default)]
+    pub struct Surface {
+        pub collidable: bool,
+        pub contact_damage: f32,
+        pub reflects_bullets: bool,
+        pub bullets_pass: bool,
+        pub restitution: f32,
+    }
+
+    impl Default for Surface {
+        fn default() -> Self {
+            Self {
+                collidable: true,
+                contact_damage: 0.,
+                reflects_bullets: false,
+                bullets_pass: false,
+                restitution: 0.,
+            }
+        }
+    }
+
+    #[derive(Deserialize)]
+    pub struct Placement {
+        pub transform: Transform,
+        pub kind: PlacementKind,
+        #[serde(default)]
+        pub properties: BTreeMap<String, PropertyValue>,
+    }
+
+    #[derive(Deserialize)]
+    pub enum PlacementKind {
+        PlayerStart,
+        EnemySpawn(EnemyKind),
+        Pickup(PickupKind),
+        Marker(String),
+    }
+
+    #[derive(Deserialize)]
+    pub enum EnemyKind {
+        Amoeba,
+    }
+
+    #[derive(Deserialize)]
+    pub enum PickupKind {
+        Health,
+    }
+
+    #[derive(Deserialize)]
+    pub enum PropertyValue {
+        Bool(bool),
+        Number(f32),
+        Text(String),
+    }
+
+    impl Surface {
+        fn upgrade(self) -> NextSurface {
+            NextSurface {
+                collidable: self.collidable,
+                contact_damage: self.contact_damage,
+                reflects_bullets: self.reflects_bullets,
+                bullets_pass: self.bullets_pass,
+                restitution: self.restitution,
+                ..default()
+            }
+        }
+    }
+
+    impl Placement {
+        fn upgrade(self) -> next::Placement {
+            let kind = match self.kind {
+                PlacementKind::PlayerStart => next::PlacementKind::PlayerStart,
+                PlacementKind::EnemySpawn(EnemyKind::Amoeba) => {
+                    next::PlacementKind::EnemySpawn(NextEnemyKind::Amoeba)
+                }
+                PlacementKind::Pickup(PickupKind::Health) => {
+                    next::PlacementKind::Pickup(NextPickupKind::Health)
+                }
+                PlacementKind::Marker(name) => next::PlacementKind::Marker(name),
+            };
+
+            let properties = self
+                .properties
+                .into_iter()
+                .map(|(key, value)| {
+                    let value = match value {
+                        PropertyValue::Bool(value) => next::PropertyValue::Bool(value),
+                        PropertyValue::Number(value) => next::PropertyValue::Number(value),
+                        PropertyValue::Text(value) => next::PropertyValue::Text(value),
+                    };
+                    (key, value)
+                })
+                .collect();
+
+            next::Placement {
+                transform: self.transform,
+                kind,
+                properties,
+            }
+        }
+    }
+
+    impl Map {
+        pub fn upgrade(self) -> next::Map {
+            next::Map {
+                version: 2,
+                map_meshes: self
+                    .map_meshes
+                    .into_iter()
+                    .map(|mesh| {
+                        // Anything brighter than 1 can only have come from the intensity slider
+                        let [r, g, b, a] = mesh.lines.color.as_rgba_f32();
+                        let intensity = r.max(g).max(b).max(1.);
+
+                        next::MapMesh {
+                            transform: mesh.transform,
+                            lines: mesh.lines.lines,
+                            primitives: vec![],
+                            includes: vec![],
+                            colors: ColorSpec::Uniform(Color::rgba(
+                                r / intensity,
+                                g / intensity,
+                                b / intensity,
+                                a,
+                            )),
+                            intensity,
+                            surface: mesh.surface.upgrade(),
+                            source: None,
+                        }
+                    })
+                    .collect(),
+                placements: self
+                    .placements
+                    .into_iter()
+                    .map(Placement::upgrade)
+                    .collect(),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::prelude::*;
+
+    use super::*;
+    use crate::{
+        assets::map::PlacementKind,
+        enemy::EnemyKind,
+        line_material::ColorSpec,
+        surface::{Surface, WallShape},
+    };
+
+    #[test]
+    fn migrates_v0() {
+        let map = migrate(include_bytes!("fixtures/v0.map.ron")).unwrap();
+
+        assert_eq!(map.version, MAP_FORMAT_VERSION);
+        assert!(map.placements.is_empty());
+
+        let [mesh] = &map.map_meshes[..] else {
+            panic!("expected one mesh, got {}", map.map_meshes.len());
+        };
+        assert_eq!(mesh.transform.translation, Vec3::new(4., -2., 0.));
+        assert_eq!(mesh.lines.len(), 2);
+        assert_eq!(mesh.intensity, 2.);
+        assert_eq!(mesh.colors, ColorSpec::Uniform(Color::rgba(1., 0.5, 0.25, 1.)));
+        assert!(mesh.surface.is_default());
+    }
+
+    #[test]
+    fn migrates_v1() {
+        let map = migrate(include_bytes!("fixtures/v1.map.ron")).unwrap();
+
+        assert_eq!(map.version, MAP_FORMAT_VERSION);
+
+        let [mesh] = &map.map_meshes[..] else {
+            panic!("expected one mesh, got {}", map.map_meshes.len());
+        };
+        assert_eq!(mesh.lines, vec![(Vec3::new(-1., 0., 0.), Vec3::new(1., 0., 0.))]);
+
+        // Colors that were never brightened keep an intensity of 1
+        assert_eq!(mesh.intensity, 1.);
+        assert_eq!(mesh.colors, ColorSpec::Uniform(Color::rgba(0.5, 0.25, 1., 1.)));
+
+        assert_eq!(mesh.surface, Surface {
+            contact_damage: 3.,
+            bullets_pass: true,
+            ..default()
+        });
+        assert_eq!(mesh.surface.shape, WallShape::Polyline);
+
+        let [start, spawn, marker] = &map.placements[..] else {
+            panic!("expected three placements, got {}", map.placements.len());
+        };
+        assert!(matches!(start.kind, PlacementKind::PlayerStart));
+        assert!(matches!(spawn.kind, PlacementKind::EnemySpawn(EnemyKind::Amoeba)));
+        assert_eq!(spawn.transform.translation, Vec3::new(3., 5., 0.));
+        assert!(matches!(marker.kind, PlacementKind::Marker(ref name) if name == "gate"));
+        assert_eq!(marker.text("route"), Some("east"));
+        assert_eq!(marker.number("waypoint"), Some(2.));
+    }
+}
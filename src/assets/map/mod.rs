@@ -0,0 +1,270 @@
+use std::collections::BTreeMap;
+
+use bevy::{
+    asset::{AssetLoader, LoadedAsset},
+    prelude::{Component, Transform, Vec3},
+    reflect::{TypePath, TypeUuid},
+};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use thiserror::Error;
+
+use super::{
+    model::{resolve_includes, ColoredLine, Include, ModelError},
+    primitive::Primitive,
+};
+use crate::{enemy::EnemyKind, line_material::ColorSpec, pickup::PickupKind, surface::Surface};
+
+pub mod generator;
+pub mod migration;
+
+/// Version written to the header of every saved map. Bump this (and add an upgrade step to
+/// [`migration`]) whenever a change to `Map` or `MapMesh` would break older files.
+pub const MAP_FORMAT_VERSION: u32 = 2;
+
+#[derive(Serialize, Deserialize, TypeUuid, TypePath)]
+#[uuid = "0443de5c-e5a4-4cba-a976-3912071cc8cb"]
+pub struct Map {
+    pub version: u32,
+    pub map_meshes: Vec<MapMesh>,
+    #[serde(default)]
+    pub placements: Vec<Placement>,
+}
+
+impl Map {
+    pub fn new(map_meshes: Vec<MapMesh>, placements: Vec<Placement>) -> Self {
+        Self {
+            version: MAP_FORMAT_VERSION,
+            map_meshes,
+            placements,
+        }
+    }
+
+    /// Parses a map in either encoding and any supported format version, upgrading it to the
+    /// current one and expanding its primitives
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, MapError> {
+        let mut map = migration::migrate(bytes)?;
+
+        for map_mesh in &mut map.map_meshes {
+            map_mesh.expand_primitives();
+        }
+
+        Ok(map)
+    }
+
+    pub fn to_bytes(&self, encoding: MapEncoding) -> Vec<u8> {
+        match encoding {
+            MapEncoding::Ron => ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+                .unwrap()
+                .into_bytes(),
+            MapEncoding::Binary => {
+                let mut bytes = BINARY_MAGIC.to_vec();
+                rmp_serde::encode::write_named(&mut bytes, self).unwrap();
+                bytes
+            }
+        }
+    }
+}
+
+/// Rewrites a map file in another encoding. Primitives and includes are kept as they are, so
+/// nothing is lost either way.
+pub fn convert_map(bytes: &[u8], encoding: MapEncoding) -> Result<Vec<u8>, MapError> {
+    Ok(migration::migrate(bytes)?.to_bytes(encoding))
+}
+
+/// Binary maps start with this, which can never start a RON file
+const BINARY_MAGIC: &[u8] = b"\0EMAP";
+
+/// The ways a `Map` can be written to a file. RON is meant to be read and edited by hand, while
+/// binary maps are smaller and much faster to load. Both go through [`MapLoader`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MapEncoding {
+    Ron,
+    /// MessagePack with named fields, so optional fields and migrations work like they do in RON
+    Binary,
+}
+
+impl MapEncoding {
+    pub fn extension(self) -> &'static str {
+        match self {
+            MapEncoding::Ron => "map.ron",
+            MapEncoding::Binary => "map.bin",
+        }
+    }
+
+    pub fn detect(bytes: &[u8]) -> Self {
+        match bytes.starts_with(BINARY_MAGIC) {
+            true => MapEncoding::Binary,
+            false => MapEncoding::Ron,
+        }
+    }
+
+    fn decode<T: DeserializeOwned>(self, bytes: &[u8]) -> Result<T, MapError> {
+        match self {
+            MapEncoding::Ron => Ok(ron::de::from_bytes(bytes)?),
+            MapEncoding::Binary => Ok(rmp_serde::from_slice(&bytes[BINARY_MAGIC.len()..])?),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct MapMesh {
+    pub transform: Transform,
+    pub lines: Vec<(Vec3, Vec3)>,
+    /// Expanded into `lines` when the map is loaded
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub primitives: Vec<Primitive>,
+    /// Models whose lines are added to `lines`, in their own colors, when the map is loaded
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub includes: Vec<Include>,
+    /// Colors before `intensity` is applied, listed for `lines` and then `primitives`
+    pub colors: ColorSpec,
+    pub intensity: f32,
+    #[serde(default, skip_serializing_if = "Surface::is_default")]
+    pub surface: Surface,
+    /// How the mesh was written, once its primitives and includes have been expanded
+    #[serde(skip)]
+    pub source: Option<MapMeshSource>,
+}
+
+/// What a loaded [`MapMesh`] had before its primitives and includes were expanded into its lines,
+/// so the editor can save it back the way it was written
+#[derive(Component, Clone, Debug)]
+pub struct MapMeshSource {
+    /// How many of the lines were written as lines. Those from primitives come next, and those
+    /// from includes last.
+    pub lines: usize,
+    /// How many lines the colors were listed for, which doesn't include those from includes
+    pub colored_lines: usize,
+    /// How many lines there are with everything expanded
+    pub expanded_lines: usize,
+    pub primitives: Vec<Primitive>,
+    pub includes: Vec<Include>,
+}
+
+impl MapMesh {
+    pub fn expand_primitives(&mut self) {
+        let primitives = std::mem::take(&mut self.primitives);
+        let lines = self.lines.len();
+        self.lines
+            .extend(primitives.iter().flat_map(Primitive::lines));
+
+        self.source = Some(MapMeshSource {
+            lines,
+            colored_lines: self.lines.len(),
+            expanded_lines: self.lines.len(),
+            primitives,
+            includes: self.includes.clone(),
+        });
+    }
+
+    /// Adds the lines of included models, which carry their own colors
+    pub fn add_included(&mut self, included: Vec<ColoredLine>) {
+        if included.is_empty() {
+            return;
+        }
+
+        // The wall's intensity is applied to these too, so it is taken back out. A wall with no
+        // intensity is black either way, so its included colors are kept as they are.
+        let scale = match self.intensity {
+            intensity if intensity != 0. => 1. / intensity,
+            _ => 1.,
+        };
+
+        let mut colors = self.colors.vertex_colors(self.lines.len());
+        for ((a, b), (color_a, color_b)) in included {
+            self.lines.push((a, b));
+            colors.extend([color_a, color_b].map(|color| color * scale));
+        }
+
+        self.colors = ColorSpec::from_vertex_colors(&colors);
+
+        if let Some(source) = &mut self.source {
+            source.expanded_lines = self.lines.len();
+        }
+    }
+}
+
+/// Anything besides walls that the map puts into the world
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Placement {
+    pub transform: Transform,
+    pub kind: PlacementKind,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub properties: BTreeMap<String, PropertyValue>,
+}
+
+impl Placement {
+    pub fn number(&self, key: &str) -> Option<f32> {
+        match self.properties.get(key) {
+            Some(PropertyValue::Number(value)) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn text(&self, key: &str) -> Option<&str> {
+        match self.properties.get(key) {
+            Some(PropertyValue::Text(value)) => Some(value),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub enum PlacementKind {
+    PlayerStart,
+    EnemySpawn(EnemyKind),
+    Pickup(PickupKind),
+    /// A named point for gameplay code to look up. Markers with a `route` text property and a
+    /// `waypoint` number are patrol waypoints, visited in order of `waypoint`:
+    ///
+    /// ```ron
+    /// (kind: Marker("gate"), properties: {"route": Text("east"), "waypoint": Number(1.0)}, ...)
+    /// ```
+    Marker(String),
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub enum PropertyValue {
+    Bool(bool),
+    Number(f32),
+    Text(String),
+}
+
+#[derive(Error, Debug)]
+pub enum MapError {
+    #[error("unsupported map format version {0} (newest supported is {MAP_FORMAT_VERSION})")]
+    UnsupportedVersion(u32),
+    #[error("invalid map data: {0}")]
+    Ron(#[from] ron::error::SpannedError),
+    #[error("invalid binary map data: {0}")]
+    Binary(#[from] rmp_serde::decode::Error),
+    #[error(transparent)]
+    Include(#[from] ModelError),
+}
+
+pub struct MapLoader;
+impl AssetLoader for MapLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut bevy::asset::LoadContext,
+    ) -> bevy::utils::BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+        Box::pin(async move {
+            let mut map = Map::from_bytes(bytes)?;
+
+            let parents = [load_context.path().to_owned()];
+            for map_mesh in &mut map.map_meshes {
+                let includes = std::mem::take(&mut map_mesh.includes);
+                map_mesh.add_included(resolve_includes(&includes, load_context, &parents).await?);
+            }
+
+            load_context.set_default_asset(LoadedAsset::new(map));
+
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["map.ron", "map.bin"]
+    }
+}
@@ -1,14 +1,38 @@
 use bevy::prelude::*;
 
-use self::{map::MapLoader, model::ModelLoader};
+use self::{
+    animation::{animate_model_colors, start_model_animations},
+    dxf::DxfLoader,
+    map::{Map, MapLoader},
+    model::{Model, ModelLoader},
+    obj::ObjLoader,
+    svg::SvgLoader,
+    tiled::TiledLoader,
+};
 
+pub mod animation;
+pub mod dxf;
 pub mod map;
 pub mod model;
+pub mod obj;
+pub mod primitive;
+pub mod svg;
+pub mod thumbnail;
+pub mod tiled;
+pub mod validation;
 
 pub struct AssetsPlugin;
 impl Plugin for AssetsPlugin {
     fn build(&self, app: &mut App) {
-        app.add_asset_loader(MapLoader)
-            .add_asset_loader(ModelLoader);
+        app.add_asset::<Map>()
+            .add_asset_loader(MapLoader)
+            .add_asset_loader(DxfLoader)
+            .add_asset_loader(TiledLoader)
+            .add_asset::<Model>()
+            .register_asset_reflect::<Model>()
+            .add_asset_loader(ModelLoader)
+            .add_asset_loader(SvgLoader)
+            .add_asset_loader(ObjLoader)
+            .add_systems(Update, (start_model_animations, animate_model_colors).chain());
     }
 }
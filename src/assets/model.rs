@@ -1,23 +1,290 @@
+use std::{collections::BTreeMap, path::PathBuf};
+
 use bevy::{
-    asset::{AssetLoader, LoadedAsset},
+    asset::{AssetIoError, AssetLoader, LoadContext, LoadedAsset},
+    math::Affine3A,
     prelude::*,
     reflect::TypeUuid,
-    render::render_resource::PrimitiveTopology,
+    render::{primitives::Aabb, render_resource::PrimitiveTopology},
+    utils::{BoxedFuture, HashMap},
 };
 use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use super::{animation::ModelAnimation, primitive::Primitive};
+use crate::line_material::ColorSpec;
 
-#[derive(Serialize, Deserialize, TypeUuid)]
-#[uuid = "0443de5c-e5a4-4cba-a976-3912071cc8cb"]
+/// A line model as written in a `.mdl.ron` file. Loading one also produces its mesh, which can be
+/// loaded directly with the `#mesh` label.
+///
+/// Lines can be given as `lines`, pairs of points, as `edges` between indices into a shared list
+/// of `vertices`, or as `primitives`. They may be mixed, in which case colors are listed for
+/// `lines` first, then `edges`, then `primitives`. Lines from `includes` keep their own colors.
+#[derive(Serialize, Deserialize, Reflect, TypeUuid, Default)]
+#[uuid = "552b504a-2d54-487b-be5d-1e1273624348"]
 pub struct Model {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub lines: Vec<(Vec3, Vec3)>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub vertices: Vec<Vec3>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub edges: Vec<(u32, u32)>,
+    /// Points of interest, such as where a weapon is mounted
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    #[reflect(ignore)]
+    pub named_vertices: BTreeMap<String, u32>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub primitives: Vec<Primitive>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub includes: Vec<Include>,
     pub colors: ColorSpec,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    #[reflect(ignore)]
+    pub animations: BTreeMap<String, ModelAnimation>,
+    #[serde(default)]
+    pub metadata: ModelMetadata,
+    /// The lines of every included model with their vertex colors, filled in by the loader
+    #[serde(skip)]
+    #[reflect(ignore)]
+    pub included: Vec<ColoredLine>,
+    #[serde(skip)]
+    pub bounds: Aabb,
+    #[serde(skip)]
+    pub mesh: Handle<Mesh>,
+}
+
+/// A line and the colors of both of its ends
+pub type ColoredLine = ((Vec3, Vec3), (Color, Color));
+
+/// Another model drawn as part of this one. Its path is relative to the assets folder, like the
+/// paths given to the `AssetServer`.
+#[derive(Serialize, Deserialize, Reflect, Clone, Debug, PartialEq)]
+pub struct Include {
+    pub model: String,
+    #[serde(default)]
+    pub transform: Transform,
+    /// Replaces the colors of every line in the included model
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color: Option<Color>,
+    /// Applied before `transform`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mirror: Option<Mirror>,
+}
+
+/// The axis that is flipped when mirroring an included model
+#[derive(Serialize, Deserialize, Reflect, Clone, Copy, Debug, PartialEq)]
+pub enum Mirror {
+    X,
+    Y,
+    Z,
 }
 
-#[derive(Serialize, Deserialize)]
-pub enum ColorSpec {
-    Uniform(Color),
-    PerLine(Vec<Color>),
-    PerVertex(Vec<(Color, Color)>),
+impl Mirror {
+    fn scale(self) -> Vec3 {
+        match self {
+            Mirror::X => Vec3::new(-1., 1., 1.),
+            Mirror::Y => Vec3::new(1., -1., 1.),
+            Mirror::Z => Vec3::new(1., 1., -1.),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Reflect, Default, Clone, Debug)]
+pub struct ModelMetadata {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub author: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+}
+
+impl Model {
+    /// Builds an indexed model, sharing every vertex that appears in more than one line
+    pub fn from_lines(lines: &[(Vec3, Vec3)], colors: ColorSpec) -> Self {
+        let mut vertices = vec![];
+        let mut indices = HashMap::new();
+
+        let mut index_of = |point: Vec3| {
+            *indices
+                .entry(point.to_array().map(f32::to_bits))
+                .or_insert_with(|| {
+                    vertices.push(point);
+                    vertices.len() as u32 - 1
+                })
+        };
+
+        let edges = lines
+            .iter()
+            .map(|(a, b)| (index_of(*a), index_of(*b)))
+            .collect();
+
+        Self { vertices, edges, colors, ..default() }
+    }
+
+    /// Every line in the model, in the order their colors are listed
+    pub fn lines(&self) -> Vec<(Vec3, Vec3)> {
+        let edges = self
+            .edges
+            .iter()
+            .map(|(a, b)| (self.vertices[*a as usize], self.vertices[*b as usize]));
+
+        let primitives = self.primitives.iter().flat_map(Primitive::lines);
+
+        self.lines
+            .iter()
+            .copied()
+            .chain(edges)
+            .chain(primitives)
+            .collect()
+    }
+
+    /// Every line in the model with its vertex colors, followed by those of included models
+    pub fn colored_lines(&self) -> Vec<ColoredLine> {
+        let lines = self.lines();
+        let colors = self.colors.vertex_colors(lines.len());
+        let colors = colors.chunks_exact(2).map(|pair| (pair[0], pair[1]));
+
+        lines
+            .into_iter()
+            .zip(colors)
+            .chain(self.included.iter().copied())
+            .collect()
+    }
+
+    pub fn validate(&self) -> Result<(), ModelError> {
+        let vertex_count = self.vertices.len();
+        let out_of_range = |index: u32| index as usize >= vertex_count;
+
+        if let Some(&(a, b)) = self
+            .edges
+            .iter()
+            .find(|(a, b)| out_of_range(*a) || out_of_range(*b))
+        {
+            return Err(ModelError::EdgeOutOfRange { a, b, vertex_count });
+        }
+
+        if let Some((name, &index)) = self.named_vertices.iter().find(|(_, i)| out_of_range(**i)) {
+            return Err(ModelError::NamedVertexOutOfRange {
+                name: name.clone(),
+                index,
+                vertex_count,
+            });
+        }
+
+        Ok(())
+    }
+
+    pub fn to_mesh(&self) -> Mesh {
+        let mut mesh = Mesh::new(PrimitiveTopology::LineList);
+
+        let (vertices, colors): (Vec<_>, Vec<_>) = self
+            .colored_lines()
+            .into_iter()
+            .flat_map(|((a, b), (color_a, color_b))| {
+                [(a, color_a.as_rgba_f32()), (b, color_b.as_rgba_f32())]
+            })
+            .unzip();
+
+        mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, vertices);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_COLOR, colors);
+        mesh
+    }
+
+    pub fn compute_bounds(&self) -> Aabb {
+        let mut points = self
+            .colored_lines()
+            .into_iter()
+            .flat_map(|((a, b), _)| [a, b]);
+        let Some(first) = points.next() else {
+            return Aabb::default();
+        };
+
+        let (min, max) = points.fold((first, first), |(min, max), p| (min.min(p), max.max(p)));
+        Aabb::from_min_max(min, max)
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum ModelError {
+    #[error("edge ({a}, {b}) refers to a vertex past the end of the {vertex_count} vertices")]
+    EdgeOutOfRange { a: u32, b: u32, vertex_count: usize },
+    #[error("named vertex {name:?} refers to vertex {index}, but there are only {vertex_count}")]
+    NamedVertexOutOfRange {
+        name: String,
+        index: u32,
+        vertex_count: usize,
+    },
+    #[error("{0:?} includes itself")]
+    IncludeCycle(PathBuf),
+    #[error("could not read included model: {0}")]
+    Io(#[from] AssetIoError),
+    #[error("invalid included model: {0}")]
+    Ron(#[from] ron::error::SpannedError),
+}
+
+/// Reads the given models and everything they include in turn, through the load context so the
+/// asset being loaded is reloaded whenever one of them changes. `parents` are the paths of the
+/// models doing the including, to catch cycles.
+pub async fn resolve_includes(
+    includes: &[Include],
+    load_context: &LoadContext<'_>,
+    parents: &[PathBuf],
+) -> Result<Vec<ColoredLine>, ModelError> {
+    let mut lines = vec![];
+
+    for include in includes {
+        lines.extend(resolve_include(include, load_context, parents).await?);
+    }
+
+    Ok(lines)
+}
+
+fn resolve_include<'a>(
+    include: &'a Include,
+    load_context: &'a LoadContext<'_>,
+    parents: &'a [PathBuf],
+) -> BoxedFuture<'a, Result<Vec<ColoredLine>, ModelError>> {
+    Box::pin(async move {
+        let path = PathBuf::from(&include.model);
+        if parents.contains(&path) {
+            return Err(ModelError::IncludeCycle(path));
+        }
+
+        let bytes = load_context.read_asset_bytes(&path).await?;
+        let mut model: Model = ron::de::from_bytes(&bytes)?;
+        model.validate()?;
+
+        let parents = [parents, &[path]].concat();
+        model.included = resolve_includes(&model.includes, load_context, &parents).await?;
+
+        let matrix = include.transform.compute_affine()
+            * Affine3A::from_scale(include.mirror.map_or(Vec3::ONE, Mirror::scale));
+
+        Ok(model
+            .colored_lines()
+            .into_iter()
+            .map(|((a, b), colors)| {
+                let colors = include.color.map_or(colors, |color| (color, color));
+                ((matrix.transform_point3(a), matrix.transform_point3(b)), colors)
+            })
+            .collect())
+    })
+}
+
+/// Sets a loaded model as the default asset, along with the mesh and animation clips built from it
+pub fn set_model_asset(mut model: Model, load_context: &mut LoadContext) {
+    model.bounds = model.compute_bounds();
+    model.mesh = load_context.set_labeled_asset("mesh", LoadedAsset::new(model.to_mesh()));
+
+    for (name, animation) in &mut model.animations {
+        animation.clip = load_context.set_labeled_asset(
+            &format!("animations/{name}"),
+            LoadedAsset::new(animation.to_clip()),
+        );
+    }
+
+    load_context.set_default_asset(LoadedAsset::new(model));
 }
 
 pub struct ModelLoader;
@@ -28,27 +295,13 @@ impl AssetLoader for ModelLoader {
         load_context: &'a mut bevy::asset::LoadContext,
     ) -> bevy::utils::BoxedFuture<'a, Result<(), bevy::asset::Error>> {
         Box::pin(async move {
-            let model: Model = ron::de::from_bytes(bytes)?;
-            let mut mesh = Mesh::new(PrimitiveTopology::LineList);
-
-            let colors: Vec<_> = match model.colors {
-                ColorSpec::PerVertex(colors) => colors
-                    .iter()
-                    .flat_map(|(a, b)| [a.as_rgba_f32(), b.as_rgba_f32()])
-                    .collect(),
-                ColorSpec::PerLine(colors) => colors
-                    .into_iter()
-                    .flat_map(|c| [c.as_rgba_f32(), c.as_rgba_f32()])
-                    .collect(),
-                ColorSpec::Uniform(color) => vec![color.as_rgba_f32(); model.lines.len() * 2],
-            };
-
-            let vertices: Vec<_> = model.lines.into_iter().flat_map(|(a, b)| [a, b]).collect();
-
-            mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, vertices);
-            mesh.insert_attribute(Mesh::ATTRIBUTE_COLOR, colors);
-
-            load_context.set_default_asset(LoadedAsset::new(mesh));
+            let mut model: Model = ron::de::from_bytes(bytes)?;
+            model.validate()?;
+
+            let parents = [load_context.path().to_owned()];
+            model.included = resolve_includes(&model.includes, load_context, &parents).await?;
+
+            set_model_asset(model, load_context);
 
             Ok(())
         })
@@ -0,0 +1,156 @@
+use bevy::{
+    asset::{AssetLoader, LoadContext},
+    prelude::*,
+    utils::{BoxedFuture, HashMap},
+};
+use thiserror::Error;
+
+use super::model::{set_model_asset, ColoredLine, Model};
+use crate::line_material::ColorSpec;
+
+#[derive(Error, Debug)]
+pub enum ObjError {
+    #[error("line {line}: {message}")]
+    Syntax { line: usize, message: String },
+    #[error("line {line}: vertex {index} does not exist")]
+    VertexOutOfRange { line: usize, index: i64 },
+    #[error("OBJ files must be UTF-8")]
+    NotUtf8(#[from] std::str::Utf8Error),
+}
+
+/// Reads the points (`v`) and polylines (`l`) of a Wavefront OBJ file, which is all a line model
+/// needs. Everything else, like faces and normals, is ignored. Coordinates are used as they are,
+/// so export from Blender with Z up to keep the top view on the play plane.
+///
+/// Vertices may have a color after their position (`v x y z r g b`), as written by Blender and
+/// MeshLab.
+pub fn obj_to_model(bytes: &[u8]) -> Result<Model, ObjError> {
+    let text = std::str::from_utf8(bytes)?;
+
+    let mut vertices = vec![];
+    let mut vertex_colors = vec![];
+    let mut edges = vec![];
+
+    for (number, line) in text.lines().enumerate() {
+        let number = number + 1;
+        let syntax_error = |message: &str| ObjError::Syntax {
+            line: number,
+            message: message.to_owned(),
+        };
+
+        let mut words = line.split_whitespace();
+
+        match words.next() {
+            Some("v") => {
+                let values = words
+                    .map(str::parse::<f32>)
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(|_| syntax_error("vertex coordinates must be numbers"))?;
+
+                let color = match values[..] {
+                    // A fourth value is a weight, which only matters for curves
+                    [_, _, _] | [_, _, _, _] => None,
+                    [_, _, _, r, g, b] => Some(Color::rgb(r, g, b)),
+                    _ => return Err(syntax_error("vertices need three coordinates")),
+                };
+
+                vertex_colors.push(color);
+                vertices.push(Vec3::new(values[0], values[1], values[2]));
+            }
+            Some("l") => {
+                let indices = words
+                    .map(|word| {
+                        // Lines may also refer to texture coordinates as `v/vt`
+                        let index: i64 = word
+                            .split('/')
+                            .next()
+                            .and_then(|index| index.parse().ok())
+                            .ok_or_else(|| syntax_error("line indices must be integers"))?;
+
+                        // Indices count from 1, or back from the last vertex if negative
+                        let resolved = match index {
+                            1.. => index - 1,
+                            ..=-1 => vertices.len() as i64 + index,
+                            0 => -1,
+                        };
+
+                        if resolved < 0 || resolved >= vertices.len() as i64 {
+                            return Err(ObjError::VertexOutOfRange { line: number, index });
+                        }
+
+                        Ok(resolved as u32)
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+
+                edges.extend(indices.windows(2).map(|pair| (pair[0], pair[1])));
+            }
+            _ => {}
+        }
+    }
+
+    let colors = if vertex_colors.iter().any(Option::is_some) {
+        let color_of = |index: u32| vertex_colors[index as usize].unwrap_or(Color::WHITE);
+        ColorSpec::PerVertex(
+            edges
+                .iter()
+                .map(|(a, b)| (color_of(*a), color_of(*b)))
+                .collect(),
+        )
+    } else {
+        ColorSpec::default()
+    };
+
+    Ok(Model { vertices, edges, colors, ..default() })
+}
+
+/// Writes lines as OBJ polylines, sharing vertices that have the same position and color
+pub fn lines_to_obj(lines: &[ColoredLine]) -> String {
+    let mut obj = String::new();
+    let mut indices = HashMap::new();
+    let mut edges = vec![];
+
+    for ((a, b), (color_a, color_b)) in lines {
+        let mut index_of = |point: Vec3, color: Color| {
+            let key = (point.to_array().map(f32::to_bits), color.as_rgba_f32().map(f32::to_bits));
+            let next = indices.len() + 1;
+
+            *indices.entry(key).or_insert_with(|| {
+                let [r, g, b, _] = color.as_rgba_f32();
+                obj += &format!("v {} {} {} {r} {g} {b}\n", point.x, point.y, point.z);
+                next
+            })
+        };
+
+        edges.push((index_of(*a, *color_a), index_of(*b, *color_b)));
+    }
+
+    for (a, b) in edges {
+        obj += &format!("l {a} {b}\n");
+    }
+
+    obj
+}
+
+pub fn model_to_obj(model: &Model) -> String {
+    lines_to_obj(&model.colored_lines())
+}
+
+/// Loads the lines of `.obj` files as models
+pub struct ObjLoader;
+impl AssetLoader for ObjLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+        Box::pin(async move {
+            set_model_asset(obj_to_model(bytes)?, load_context);
+
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["obj"]
+    }
+}
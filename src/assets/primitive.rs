@@ -0,0 +1,136 @@
+use std::f32::consts::{PI, TAU};
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::utils::drawing::{arc, circle};
+
+/// A shape that is described by a few parameters instead of its segments, expanded into lines on
+/// the play plane when the model or map containing it is loaded. Angles are in degrees,
+/// counterclockwise from +X.
+#[derive(Serialize, Deserialize, Reflect, Clone, Debug, PartialEq)]
+pub enum Primitive {
+    Circle {
+        #[serde(default)]
+        center: Vec3,
+        radius: f32,
+        segments: usize,
+    },
+    Arc {
+        #[serde(default)]
+        center: Vec3,
+        radius: f32,
+        start: f32,
+        end: f32,
+        segments: usize,
+    },
+    /// A regular polygon with its corners on a circle of `radius`
+    Polygon {
+        #[serde(default)]
+        center: Vec3,
+        radius: f32,
+        sides: usize,
+        #[serde(default)]
+        rotation: f32,
+    },
+    Star {
+        #[serde(default)]
+        center: Vec3,
+        outer_radius: f32,
+        inner_radius: f32,
+        points: usize,
+        #[serde(default)]
+        rotation: f32,
+    },
+    Rectangle {
+        #[serde(default)]
+        center: Vec3,
+        size: Vec2,
+        #[serde(default)]
+        rotation: f32,
+    },
+    /// A Bézier curve of any degree, from the first control point to the last
+    Bezier { points: Vec<Vec3>, segments: usize },
+}
+
+impl Primitive {
+    pub fn lines(&self) -> Vec<(Vec3, Vec3)> {
+        match *self {
+            Primitive::Circle { center, radius, segments } => {
+                offset(circle(radius, segments), center)
+            }
+            Primitive::Arc { center, radius, start, end, segments } => {
+                offset(arc(radius, start.to_radians(), end.to_radians(), segments, false), center)
+            }
+            Primitive::Polygon { center, radius, sides, rotation } => {
+                let corners = (0..sides).map(|i| {
+                    let angle = rotation.to_radians() + i as f32 / sides as f32 * TAU;
+                    center + Vec3::new(angle.cos(), angle.sin(), 0.) * radius
+                });
+
+                closed(corners.collect())
+            }
+            Primitive::Star {
+                center,
+                outer_radius,
+                inner_radius,
+                points,
+                rotation,
+            } => {
+                let corners = (0..points * 2).map(|i| {
+                    let angle = rotation.to_radians() + i as f32 / points as f32 * PI;
+                    let radius = if i % 2 == 0 {
+                        outer_radius
+                    } else {
+                        inner_radius
+                    };
+                    center + Vec3::new(angle.cos(), angle.sin(), 0.) * radius
+                });
+
+                closed(corners.collect())
+            }
+            Primitive::Rectangle { center, size, rotation } => {
+                let rotation = Quat::from_rotation_z(rotation.to_radians());
+                let half = size / 2.;
+                let corners = [(1., 1.), (-1., 1.), (-1., -1.), (1., -1.)]
+                    .map(|(x, y)| center + rotation * Vec3::new(half.x * x, half.y * y, 0.));
+
+                closed(corners.to_vec())
+            }
+            Primitive::Bezier { ref points, segments } => {
+                if points.len() < 2 {
+                    return vec![];
+                }
+
+                let samples: Vec<_> = (0..=segments)
+                    .map(|i| de_casteljau(points, i as f32 / segments as f32))
+                    .collect();
+
+                samples.windows(2).map(|pair| (pair[0], pair[1])).collect()
+            }
+        }
+    }
+}
+
+fn offset(lines: Vec<(Vec3, Vec3)>, by: Vec3) -> Vec<(Vec3, Vec3)> {
+    lines.into_iter().map(|(a, b)| (a + by, b + by)).collect()
+}
+
+/// Connects each corner to the next, and the last back to the first
+fn closed(corners: Vec<Vec3>) -> Vec<(Vec3, Vec3)> {
+    let next = corners.iter().cycle().skip(1);
+    corners.iter().zip(next).map(|(a, b)| (*a, *b)).collect()
+}
+
+fn de_casteljau(points: &[Vec3], t: f32) -> Vec3 {
+    let mut points = points.to_vec();
+
+    while points.len() > 1 {
+        points = points
+            .windows(2)
+            .map(|pair| pair[0].lerp(pair[1], t))
+            .collect();
+    }
+
+    points[0]
+}
@@ -0,0 +1,319 @@
+use bevy::{
+    asset::{AssetLoader, LoadContext},
+    prelude::*,
+    utils::BoxedFuture,
+};
+use thiserror::Error;
+use usvg::{
+    tiny_skia_path::{PathSegment, Point},
+    NodeExt, NodeKind, Paint, TreeParsing,
+};
+
+use super::{
+    animation::Interpolate,
+    map::Map,
+    model::{set_model_asset, ColoredLine, Model},
+};
+use crate::line_material::{ColorSpec, LineList};
+
+/// Settings for turning SVG shapes into lines on the play plane. SVG's Y axis points down, so it
+/// is flipped to point up like ours.
+pub struct SvgImport {
+    /// How far a flattened curve may stray from the real one, in SVG units
+    pub tolerance: f32,
+    /// World units per SVG unit
+    pub scale: f32,
+}
+
+impl Default for SvgImport {
+    fn default() -> Self {
+        Self { tolerance: 0.1, scale: 1. }
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum SvgError {
+    #[error("invalid SVG: {0}")]
+    Parse(#[from] usvg::Error),
+}
+
+impl SvgImport {
+    /// One `LineList` per visible shape, in the color of its stroke, or of its fill if it has no
+    /// stroke. Paints other than plain colors come out white.
+    pub fn line_lists(&self, data: &[u8]) -> Result<Vec<LineList>, SvgError> {
+        let tree = usvg::Tree::from_data(data, &usvg::Options::default())?;
+        let mut line_lists = vec![];
+
+        for node in tree.root.descendants() {
+            let NodeKind::Path(ref path) = *node.borrow() else {
+                continue;
+            };
+
+            if path.visibility != usvg::Visibility::Visible {
+                continue;
+            }
+
+            let Some(data) = (*path.data).clone().transform(node.abs_transform()) else {
+                continue;
+            };
+
+            let lines = self.flatten(data.segments());
+            if lines.is_empty() {
+                continue;
+            }
+
+            let color = match (&path.stroke, &path.fill) {
+                (Some(stroke), _) => paint_color(&stroke.paint, stroke.opacity.get()),
+                (None, Some(fill)) => paint_color(&fill.paint, fill.opacity.get()),
+                (None, None) => Color::WHITE,
+            };
+
+            line_lists.push(LineList { lines, color });
+        }
+
+        Ok(line_lists)
+    }
+
+    pub fn model(&self, data: &[u8]) -> Result<Model, SvgError> {
+        let mut lines = vec![];
+        let mut colors = vec![];
+
+        for line_list in self.line_lists(data)? {
+            colors.extend(std::iter::repeat(line_list.color).take(line_list.lines.len() * 2));
+            lines.extend(line_list.lines);
+        }
+
+        Ok(Model {
+            lines,
+            colors: ColorSpec::from_vertex_colors(&colors),
+            ..default()
+        })
+    }
+
+    fn flatten(&self, segments: impl Iterator<Item = PathSegment>) -> Vec<(Vec3, Vec3)> {
+        let mut points = vec![];
+        let mut lines = vec![];
+        let mut start = Vec2::ZERO;
+        let mut current = Vec2::ZERO;
+
+        for segment in segments {
+            match segment {
+                PathSegment::MoveTo(to) => {
+                    start = vec2(to);
+                    current = start;
+                    continue;
+                }
+                PathSegment::LineTo(to) => points.push(vec2(to)),
+                PathSegment::QuadTo(control, to) => {
+                    let (control, to) = (vec2(control), vec2(to));
+                    let cubic = [
+                        current,
+                        current + (control - current) * 2. / 3.,
+                        to + (control - to) * 2. / 3.,
+                        to,
+                    ];
+                    flatten_cubic(cubic, self.tolerance, 0, &mut points);
+                }
+                PathSegment::CubicTo(a, b, to) => {
+                    let cubic = [current, vec2(a), vec2(b), vec2(to)];
+                    flatten_cubic(cubic, self.tolerance, 0, &mut points);
+                }
+                PathSegment::Close => points.push(start),
+            }
+
+            for point in points.drain(..) {
+                if point != current {
+                    lines.push((self.to_world(current), self.to_world(point)));
+                }
+                current = point;
+            }
+        }
+
+        lines
+    }
+
+    fn to_world(&self, point: Vec2) -> Vec3 {
+        Vec3::new(point.x, -point.y, 0.) * self.scale
+    }
+}
+
+fn vec2(point: Point) -> Vec2 {
+    Vec2::new(point.x, point.y)
+}
+
+fn paint_color(paint: &Paint, opacity: f32) -> Color {
+    match paint {
+        Paint::Color(color) => {
+            Color::rgba_u8(color.red, color.green, color.blue, (opacity * 255.).round() as u8)
+        }
+        _ => Color::WHITE,
+    }
+}
+
+/// Splits a cubic Bézier curve in half until both its control points are within `tolerance` of
+/// the line between its ends, adding the end of each flat enough piece to `points`
+fn flatten_cubic(curve: [Vec2; 4], tolerance: f32, depth: u32, points: &mut Vec<Vec2>) {
+    let [a, b, c, d] = curve;
+
+    let distance_to_chord = |point: Vec2| {
+        let chord = d - a;
+        if chord.length_squared() < f32::EPSILON {
+            point.distance(a)
+        } else {
+            (point - a).perp_dot(chord).abs() / chord.length()
+        }
+    };
+
+    if depth >= 16 || (distance_to_chord(b) <= tolerance && distance_to_chord(c) <= tolerance) {
+        points.push(d);
+        return;
+    }
+
+    let ab = a.lerp(b, 0.5);
+    let bc = b.lerp(c, 0.5);
+    let cd = c.lerp(d, 0.5);
+    let abc = ab.lerp(bc, 0.5);
+    let bcd = bc.lerp(cd, 0.5);
+    let middle = abc.lerp(bcd, 0.5);
+
+    flatten_cubic([a, ab, abc, middle], tolerance, depth + 1, points);
+    flatten_cubic([middle, bcd, cd, d], tolerance, depth + 1, points);
+}
+
+/// Draws a map as seen from above, with its placements as labeled circles
+pub fn map_to_svg(map: &Map) -> String {
+    let lines: Vec<_> = map
+        .map_meshes
+        .iter()
+        .flat_map(|map_mesh| {
+            let matrix = map_mesh.transform.compute_affine();
+            let colors = map_mesh.colors.vertex_colors(map_mesh.lines.len());
+            let colors: Vec<_> = colors
+                .chunks_exact(2)
+                .map(|pair| (pair[0], pair[1]))
+                .collect();
+
+            map_mesh
+                .lines
+                .iter()
+                .zip(colors)
+                .map(move |((a, b), colors)| {
+                    ((matrix.transform_point3(*a), matrix.transform_point3(*b)), colors)
+                })
+        })
+        .collect();
+
+    let points: Vec<_> = map
+        .placements
+        .iter()
+        .map(|placement| (placement.transform.translation, format!("{:?}", placement.kind)))
+        .collect();
+
+    write_svg(&lines, &points)
+}
+
+/// Draws a model as seen from above
+pub fn model_to_svg(model: &Model) -> String {
+    write_svg(&model.colored_lines(), &[])
+}
+
+/// Projects the lines onto the play plane and writes them out as an SVG document on a black
+/// background. Strokes stay one pixel wide however far the image is zoomed.
+fn write_svg(lines: &[ColoredLine], points: &[(Vec3, String)]) -> String {
+    let to_svg = |point: Vec3| Vec2::new(point.x, -point.y);
+
+    let mut corners = lines
+        .iter()
+        .flat_map(|((a, b), _)| [*a, *b])
+        .chain(points.iter().map(|(point, _)| *point))
+        .map(to_svg);
+
+    let (min, max) = match corners.next() {
+        Some(first) => corners.fold((first, first), |(min, max), p| (min.min(p), max.max(p))),
+        None => (Vec2::ZERO, Vec2::ONE),
+    };
+
+    let margin = ((max - min).max_element() * 0.05).max(1.);
+    let (min, size) = (min - margin, max - min + margin * 2.);
+
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"{:.3} {:.3} {:.3} {:.3}\">\n",
+        min.x, min.y, size.x, size.y
+    );
+
+    svg += &format!(
+        "  <rect x=\"{:.3}\" y=\"{:.3}\" width=\"{:.3}\" height=\"{:.3}\" fill=\"black\"/>\n",
+        min.x, min.y, size.x, size.y
+    );
+
+    svg += "  <g fill=\"none\" stroke-linecap=\"round\">\n";
+    for ((a, b), (color_a, color_b)) in lines {
+        let (a, b) = (to_svg(*a), to_svg(*b));
+        svg += &format!(
+            "    <line x1=\"{:.3}\" y1=\"{:.3}\" x2=\"{:.3}\" y2=\"{:.3}\" {} \
+             vector-effect=\"non-scaling-stroke\"/>\n",
+            a.x,
+            a.y,
+            b.x,
+            b.y,
+            stroke(color_a.interpolate(*color_b, 0.5)),
+        );
+    }
+    svg += "  </g>\n";
+
+    for (point, label) in points {
+        let point = to_svg(*point);
+        svg += &format!(
+            "  <circle cx=\"{:.3}\" cy=\"{:.3}\" r=\"0.5\" fill=\"none\" stroke=\"white\" \
+             vector-effect=\"non-scaling-stroke\"><title>{}</title></circle>\n",
+            point.x,
+            point.y,
+            escape(label),
+        );
+    }
+
+    svg += "</svg>\n";
+    svg
+}
+
+/// Stroke attributes for a color, scaled down if it is brighter than SVG can show
+fn stroke(color: Color) -> String {
+    let [r, g, b, a] = color.as_rgba_f32();
+    let brightest = r.max(g).max(b).max(1.);
+    let [r, g, b] = [r, g, b].map(|c| (c / brightest * 255.).round() as u8);
+
+    let mut stroke = format!("stroke=\"#{r:02x}{g:02x}{b:02x}\"");
+    if a < 1. {
+        stroke += &format!(" stroke-opacity=\"{a:.3}\"");
+    }
+
+    stroke
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Loads `.svg` files as models, using the default [`SvgImport`] settings
+pub struct SvgLoader;
+impl AssetLoader for SvgLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+        Box::pin(async move {
+            let model = SvgImport::default().model(bytes)?;
+            set_model_asset(model, load_context);
+
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["svg"]
+    }
+}
@@ -0,0 +1,235 @@
+use bevy::prelude::*;
+use image::RgbaImage;
+
+use super::{map::Map, model::Model};
+use crate::utils::drawing::circle;
+
+/// Which way a thumbnail looks at its lines. Drawings are always orthographic.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct View {
+    /// Degrees counterclockwise around the Z axis
+    pub yaw: f32,
+    /// Degrees tilted away from looking straight down, so 90 looks at the side
+    pub pitch: f32,
+}
+
+impl View {
+    pub const TOP: View = View { yaw: 0., pitch: 0. };
+    pub const FRONT: View = View { yaw: 0., pitch: 90. };
+    pub const SIDE: View = View { yaw: 90., pitch: 90. };
+    pub const ISOMETRIC: View = View { yaw: 45., pitch: 54.7356 };
+
+    /// Turns the world so the view looks down its Z axis
+    fn rotation(self) -> Quat {
+        Quat::from_rotation_x(-self.pitch.to_radians())
+            * Quat::from_rotation_z(-self.yaw.to_radians())
+    }
+}
+
+/// Settings for drawing maps and models into images on the CPU, for previews and icons
+pub struct Thumbnail {
+    pub width: u32,
+    pub height: u32,
+    pub view: View,
+    /// How much bright lines glow, like the game's bloom. 0 turns it off.
+    pub bloom: f32,
+    /// How much of the image is left empty around the drawing, as a fraction of its size
+    pub margin: f32,
+    /// Draws the map's placements as small gray circles
+    pub placements: bool,
+}
+
+impl Default for Thumbnail {
+    fn default() -> Self {
+        Self {
+            width: 256,
+            height: 256,
+            view: View::TOP,
+            bloom: 1.,
+            margin: 0.05,
+            placements: true,
+        }
+    }
+}
+
+/// A line with a linear color for each end, which may be brighter than 1
+type GlowingLine = ((Vec3, Vec3), (Vec3, Vec3));
+
+impl Thumbnail {
+    /// Draws a map without the lines of the models it includes, which need the asset server
+    pub fn map(&self, map: &Map) -> RgbaImage {
+        let mut lines = vec![];
+
+        for map_mesh in &map.map_meshes {
+            let matrix = map_mesh.transform.compute_affine();
+            let colors = map_mesh.colors.vertex_colors(map_mesh.lines.len());
+            let glow = |color: Color| {
+                let [r, g, b, _] = (color * map_mesh.intensity).as_linear_rgba_f32();
+                Vec3::new(r, g, b)
+            };
+
+            for (&(a, b), colors) in map_mesh.lines.iter().zip(colors.chunks_exact(2)) {
+                lines.push((
+                    (matrix.transform_point3(a), matrix.transform_point3(b)),
+                    (glow(colors[0]), glow(colors[1])),
+                ));
+            }
+        }
+
+        if self.placements {
+            let gray = Vec3::splat(0.3);
+
+            for placement in &map.placements {
+                let center = placement.transform.translation;
+                lines.extend(
+                    circle(0.5, 12)
+                        .into_iter()
+                        .map(|(a, b)| ((center + a, center + b), (gray, gray))),
+                );
+            }
+        }
+
+        self.draw(&lines)
+    }
+
+    /// Draws a model without the lines of the models it includes, which need the asset server
+    pub fn model(&self, model: &Model) -> RgbaImage {
+        // Model colors go to the GPU as they are, so they are already linear
+        let glow = |color: Color| {
+            let [r, g, b, _] = color.as_rgba_f32();
+            Vec3::new(r, g, b)
+        };
+
+        let lines: Vec<_> = model
+            .colored_lines()
+            .into_iter()
+            .map(|(line, (a, b))| (line, (glow(a), glow(b))))
+            .collect();
+
+        self.draw(&lines)
+    }
+
+    fn draw(&self, lines: &[GlowingLine]) -> RgbaImage {
+        let (width, height) = (self.width.max(1) as usize, self.height.max(1) as usize);
+        let size = Vec2::new(width as f32, height as f32);
+
+        let rotation = self.view.rotation();
+        let lines: Vec<_> = lines
+            .iter()
+            .map(|&((a, b), colors)| {
+                (((rotation * a).truncate(), (rotation * b).truncate()), colors)
+            })
+            .filter(|((a, b), _)| a.is_finite() && b.is_finite())
+            .collect();
+
+        // Fit the drawing into the image, keeping its proportions
+        let mut points = lines.iter().flat_map(|((a, b), _)| [*a, *b]);
+        let (min, max) = match points.next() {
+            Some(first) => points.fold((first, first), |(min, max), p| (min.min(p), max.max(p))),
+            None => (Vec2::ZERO, Vec2::ONE),
+        };
+
+        let drawing_size = (max - min).max(Vec2::splat(f32::EPSILON));
+        let scale = (size * (1. - 2. * self.margin) / drawing_size).min_element();
+        let center = (min + max) / 2.;
+        let to_pixels = |point: Vec2| {
+            let point = (point - center) * scale;
+            Vec2::new(size.x / 2. + point.x, size.y / 2. - point.y)
+        };
+
+        let mut pixels = vec![Vec3::ZERO; width * height];
+
+        for &((a, b), (color_a, color_b)) in &lines {
+            let (a, b) = (to_pixels(a), to_pixels(b));
+
+            // Two samples per pixel, each spread over the four pixels around it
+            let samples = (a.distance(b) * 2.).ceil().max(1.) as usize;
+            for sample in 0..=samples {
+                let t = sample as f32 / samples as f32;
+                let point = a.lerp(b, t) - 0.5;
+                let color = color_a.lerp(color_b, t) * 0.5;
+
+                let corner = point.floor();
+                let fraction = point - corner;
+
+                for (dx, dy, weight) in [
+                    (0, 0, (1. - fraction.x) * (1. - fraction.y)),
+                    (1, 0, fraction.x * (1. - fraction.y)),
+                    (0, 1, (1. - fraction.x) * fraction.y),
+                    (1, 1, fraction.x * fraction.y),
+                ] {
+                    let x = corner.x as i64 + dx;
+                    let y = corner.y as i64 + dy;
+
+                    if (0..width as i64).contains(&x) && (0..height as i64).contains(&y) {
+                        pixels[y as usize * width + x as usize] += color * weight;
+                    }
+                }
+            }
+        }
+
+        if self.bloom > 0. {
+            // A few blurs of different sizes give a glow that fades out slowly, like the game's
+            let extent = width.max(height) as f32;
+            let mut glow = vec![Vec3::ZERO; pixels.len()];
+
+            for (sigma, weight) in [(0.005, 0.5), (0.015, 0.3), (0.04, 0.2)] {
+                let blurred = gaussian_blur(&pixels, width, height, sigma * extent);
+                for (glow, blurred) in glow.iter_mut().zip(blurred) {
+                    *glow += blurred * weight * self.bloom;
+                }
+            }
+
+            for (pixel, glow) in pixels.iter_mut().zip(glow) {
+                *pixel += glow;
+            }
+        }
+
+        RgbaImage::from_fn(width as u32, height as u32, |x, y| {
+            // Bright colors approach white instead of being clipped
+            let linear = Vec3::ONE - (-pixels[y as usize * width + x as usize]).exp();
+            let [r, g, b, _] = Color::rgb_linear(linear.x, linear.y, linear.z).as_rgba_f32();
+
+            image::Rgba([r, g, b, 1.].map(|channel| (channel * 255.).round() as u8))
+        })
+    }
+}
+
+/// Three box blurs in a row, which are close to a Gaussian blur
+fn gaussian_blur(pixels: &[Vec3], width: usize, height: usize, sigma: f32) -> Vec<Vec3> {
+    let radius = ((((4. * sigma * sigma + 1.).sqrt() - 1.) / 2.).round() as usize).max(1);
+
+    let mut pixels = pixels.to_vec();
+    for _ in 0..3 {
+        pixels = box_blur(&pixels, height, width, radius, |row, i| row * width + i);
+        pixels = box_blur(&pixels, width, height, radius, |column, i| i * width + column);
+    }
+
+    pixels
+}
+
+/// Averages every pixel with the `radius` pixels to either side of it along `lines` lines of
+/// `length` pixels. `index` finds a pixel from its line and its position along it.
+fn box_blur(
+    pixels: &[Vec3],
+    lines: usize,
+    length: usize,
+    radius: usize,
+    index: impl Fn(usize, usize) -> usize,
+) -> Vec<Vec3> {
+    let mut blurred = vec![Vec3::ZERO; pixels.len()];
+    let mut sums = vec![Vec3::ZERO; length + 1];
+
+    for line in 0..lines {
+        for i in 0..length {
+            sums[i + 1] = sums[i] + pixels[index(line, i)];
+        }
+
+        for i in 0..length {
+            let (start, end) = (i.saturating_sub(radius), (i + radius + 1).min(length));
+            blurred[index(line, i)] = (sums[end] - sums[start]) / (2 * radius + 1) as f32;
+        }
+    }
+
+    blurred
+}
@@ -0,0 +1,451 @@
+use std::{collections::BTreeMap, str::FromStr};
+
+use bevy::{
+    asset::{AssetLoader, LoadContext, LoadedAsset},
+    prelude::*,
+    utils::BoxedFuture,
+};
+use serde::Deserialize;
+use thiserror::Error;
+
+use super::map::{Map, MapMesh, Placement, PlacementKind, PropertyValue};
+use crate::{line_material::ColorSpec, surface::Surface};
+
+#[derive(Error, Debug)]
+pub enum TiledError {
+    #[error("Tiled maps must be UTF-8")]
+    NotUtf8(#[from] std::str::Utf8Error),
+    #[error("invalid TMX: {0}")]
+    Xml(#[from] roxmltree::Error),
+    #[error("invalid TMJ: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("invalid {attribute:?} on <{element}>: {value:?}")]
+    Attribute {
+        element: String,
+        attribute: String,
+        value: String,
+    },
+    #[error("unknown Tiled map extension {0:?}")]
+    Extension(String),
+}
+
+/// An object layer, with the offsets of it and its parent groups already added to its objects
+struct Layer {
+    color: Option<Color>,
+    objects: Vec<Object>,
+}
+
+struct Object {
+    name: String,
+    /// Called "class" since Tiled 1.9, and "type" before
+    kind: String,
+    /// In pixels, with Y pointing down
+    position: Vec2,
+    /// Clockwise, in degrees
+    rotation: f32,
+    shape: Shape,
+    properties: BTreeMap<String, PropertyValue>,
+}
+
+enum Shape {
+    Point,
+    /// Relative to the object's position
+    Polyline(Vec<Vec2>),
+    Polygon(Vec<Vec2>),
+    /// Rectangles, ellipses, text and tiles, which aren't imported
+    Other,
+}
+
+/// Builds a map from the object layers of an orthogonal Tiled map, with one tile to a world unit.
+///
+/// Every layer's polylines and polygons become one wall, in the layer's color. Point objects with
+/// a class become placements: the class is written like a `PlacementKind` in a map file, such as
+/// `PlayerStart`, `EnemySpawn(Amoeba)` or `Pickup(Health)`, except that a plain `Marker` is named
+/// after the object. Their custom properties become the placement's properties.
+fn to_map(tile_size: Vec2, layers: Vec<Layer>) -> Map {
+    let to_world = |point: Vec2| Vec3::new(point.x / tile_size.x, -point.y / tile_size.y, 0.);
+
+    let mut map_meshes = vec![];
+    let mut placements = vec![];
+
+    for layer in layers {
+        let mut lines = vec![];
+
+        for object in layer.objects {
+            let rotation = Mat2::from_angle(object.rotation.to_radians());
+            let place = |point: &Vec2| to_world(object.position + rotation * *point);
+
+            match object.shape {
+                Shape::Polyline(points) => lines.extend(
+                    points
+                        .windows(2)
+                        .map(|pair| (place(&pair[0]), place(&pair[1]))),
+                ),
+                Shape::Polygon(points) if points.len() > 1 => lines.extend(
+                    points
+                        .iter()
+                        .zip(points.iter().cycle().skip(1))
+                        .map(|(a, b)| (place(a), place(b))),
+                ),
+                Shape::Point if !object.kind.is_empty() => {
+                    let kind = match object.kind.as_str() {
+                        "Marker" => PlacementKind::Marker(object.name),
+                        kind => match ron::from_str(kind) {
+                            Ok(kind) => kind,
+                            Err(err) => {
+                                warn!("Skipping Tiled object of unknown class {kind:?}: {err}");
+                                continue;
+                            }
+                        },
+                    };
+
+                    placements.push(Placement {
+                        transform: Transform::from_translation(to_world(object.position)),
+                        kind,
+                        properties: object.properties,
+                    });
+                }
+                _ => {}
+            }
+        }
+
+        if lines.is_empty() {
+            continue;
+        }
+
+        map_meshes.push(MapMesh {
+            transform: Transform::default(),
+            lines,
+            primitives: vec![],
+            includes: vec![],
+            colors: ColorSpec::Uniform(layer.color.unwrap_or(Color::WHITE)),
+            intensity: 1.,
+            surface: Surface::default(),
+            source: None,
+        });
+    }
+
+    Map::new(map_meshes, placements)
+}
+
+/// Parses Tiled's `#rrggbb` and `#aarrggbb` colors
+fn parse_color(color: &str) -> Option<Color> {
+    let hex = color.strip_prefix('#').unwrap_or(color);
+    let value = u32::from_str_radix(hex, 16).ok()?;
+    let [a, r, g, b] = value.to_be_bytes();
+
+    match hex.len() {
+        6 => Some(Color::rgb_u8(r, g, b)),
+        8 => Some(Color::rgba_u8(r, g, b, a)),
+        _ => None,
+    }
+}
+
+fn property_value(kind: &str, value: &str) -> PropertyValue {
+    match kind {
+        "bool" => PropertyValue::Bool(value == "true"),
+        "int" | "float" => match value.parse() {
+            Ok(number) => PropertyValue::Number(number),
+            Err(_) => PropertyValue::Text(value.to_owned()),
+        },
+        _ => PropertyValue::Text(value.to_owned()),
+    }
+}
+
+pub fn tmx_to_map(bytes: &[u8]) -> Result<Map, TiledError> {
+    let document = roxmltree::Document::parse(std::str::from_utf8(bytes)?)?;
+    let map = document.root_element();
+
+    let tile_size = Vec2::new(attribute(map, "tilewidth", 1.)?, attribute(map, "tileheight", 1.)?);
+
+    let mut layers = vec![];
+    tmx_layers(map, Vec2::ZERO, &mut layers)?;
+
+    Ok(to_map(tile_size, layers))
+}
+
+fn attribute<T: FromStr>(node: roxmltree::Node, name: &str, default: T) -> Result<T, TiledError> {
+    match node.attribute(name) {
+        Some(value) => value.parse().map_err(|_| TiledError::Attribute {
+            element: node.tag_name().name().to_owned(),
+            attribute: name.to_owned(),
+            value: value.to_owned(),
+        }),
+        None => Ok(default),
+    }
+}
+
+fn tmx_layers(
+    parent: roxmltree::Node,
+    offset: Vec2,
+    layers: &mut Vec<Layer>,
+) -> Result<(), TiledError> {
+    for node in parent.children().filter(roxmltree::Node::is_element) {
+        if !matches!(node.tag_name().name(), "group" | "objectgroup") {
+            continue;
+        }
+
+        if attribute(node, "visible", 1)? == 0 {
+            continue;
+        }
+
+        let offset =
+            offset + Vec2::new(attribute(node, "offsetx", 0.)?, attribute(node, "offsety", 0.)?);
+
+        if node.has_tag_name("group") {
+            tmx_layers(node, offset, layers)?;
+            continue;
+        }
+
+        let mut objects = vec![];
+        for object in node.children().filter(|node| node.has_tag_name("object")) {
+            if attribute(object, "visible", 1)? != 0 {
+                objects.push(tmx_object(object, offset)?);
+            }
+        }
+
+        layers.push(Layer {
+            color: node.attribute("color").and_then(parse_color),
+            objects,
+        });
+    }
+
+    Ok(())
+}
+
+fn tmx_object(node: roxmltree::Node, offset: Vec2) -> Result<Object, TiledError> {
+    let mut object = Object {
+        name: node.attribute("name").unwrap_or_default().to_owned(),
+        kind: node
+            .attribute("class")
+            .or(node.attribute("type"))
+            .unwrap_or_default()
+            .to_owned(),
+        position: offset + Vec2::new(attribute(node, "x", 0.)?, attribute(node, "y", 0.)?),
+        rotation: attribute(node, "rotation", 0.)?,
+        shape: Shape::Other,
+        properties: BTreeMap::new(),
+    };
+
+    for child in node.children().filter(roxmltree::Node::is_element) {
+        match child.tag_name().name() {
+            "point" => object.shape = Shape::Point,
+            "polyline" => object.shape = Shape::Polyline(tmx_points(child)?),
+            "polygon" => object.shape = Shape::Polygon(tmx_points(child)?),
+            "properties" => {
+                for property in child
+                    .children()
+                    .filter(|node| node.has_tag_name("property"))
+                {
+                    // Multi-line strings are stored as text instead of a value
+                    let value = property
+                        .attribute("value")
+                        .or(property.text())
+                        .unwrap_or_default();
+
+                    object.properties.insert(
+                        property.attribute("name").unwrap_or_default().to_owned(),
+                        property_value(property.attribute("type").unwrap_or("string"), value),
+                    );
+                }
+            }
+            _ => {}
+        }
+    }
+
+    Ok(object)
+}
+
+/// Reads a list of points written as `x,y x,y ...`
+fn tmx_points(node: roxmltree::Node) -> Result<Vec<Vec2>, TiledError> {
+    let points = node.attribute("points").unwrap_or_default();
+    let invalid = || TiledError::Attribute {
+        element: node.tag_name().name().to_owned(),
+        attribute: "points".to_owned(),
+        value: points.to_owned(),
+    };
+
+    points
+        .split_whitespace()
+        .map(|point| {
+            let (x, y) = point.split_once(',').ok_or_else(invalid)?;
+            Ok(Vec2::new(x.parse().map_err(|_| invalid())?, y.parse().map_err(|_| invalid())?))
+        })
+        .collect()
+}
+
+#[derive(Deserialize)]
+struct TmjMap {
+    tilewidth: f32,
+    tileheight: f32,
+    layers: Vec<TmjLayer>,
+}
+
+#[derive(Deserialize)]
+struct TmjLayer {
+    #[serde(rename = "type")]
+    kind: String,
+    #[serde(default = "visible")]
+    visible: bool,
+    #[serde(default)]
+    offsetx: f32,
+    #[serde(default)]
+    offsety: f32,
+    color: Option<String>,
+    #[serde(default)]
+    objects: Vec<TmjObject>,
+    /// The children of a group layer
+    #[serde(default)]
+    layers: Vec<TmjLayer>,
+}
+
+#[derive(Deserialize)]
+struct TmjObject {
+    #[serde(default)]
+    name: String,
+    #[serde(rename = "type", default)]
+    kind: String,
+    #[serde(default)]
+    class: String,
+    x: f32,
+    y: f32,
+    #[serde(default)]
+    rotation: f32,
+    #[serde(default = "visible")]
+    visible: bool,
+    #[serde(default)]
+    point: bool,
+    polyline: Option<Vec<TmjPoint>>,
+    polygon: Option<Vec<TmjPoint>>,
+    #[serde(default)]
+    properties: Vec<TmjProperty>,
+}
+
+#[derive(Deserialize)]
+struct TmjPoint {
+    x: f32,
+    y: f32,
+}
+
+#[derive(Deserialize)]
+struct TmjProperty {
+    name: String,
+    #[serde(rename = "type", default)]
+    kind: String,
+    value: serde_json::Value,
+}
+
+fn visible() -> bool {
+    true
+}
+
+pub fn tmj_to_map(bytes: &[u8]) -> Result<Map, TiledError> {
+    let map: TmjMap = serde_json::from_slice(bytes)?;
+
+    let mut layers = vec![];
+    tmj_layers(map.layers, Vec2::ZERO, &mut layers);
+
+    Ok(to_map(Vec2::new(map.tilewidth, map.tileheight), layers))
+}
+
+fn tmj_layers(children: Vec<TmjLayer>, offset: Vec2, layers: &mut Vec<Layer>) {
+    for layer in children {
+        if !layer.visible {
+            continue;
+        }
+
+        let offset = offset + Vec2::new(layer.offsetx, layer.offsety);
+
+        match layer.kind.as_str() {
+            "group" => tmj_layers(layer.layers, offset, layers),
+            "objectgroup" => layers.push(Layer {
+                color: layer.color.as_deref().and_then(parse_color),
+                objects: layer
+                    .objects
+                    .into_iter()
+                    .filter(|object| object.visible)
+                    .map(|object| tmj_object(object, offset))
+                    .collect(),
+            }),
+            _ => {}
+        }
+    }
+}
+
+fn tmj_object(object: TmjObject, offset: Vec2) -> Object {
+    let points = |points: Vec<TmjPoint>| {
+        points
+            .into_iter()
+            .map(|point| Vec2::new(point.x, point.y))
+            .collect()
+    };
+
+    let shape = match (object.point, object.polyline, object.polygon) {
+        (true, _, _) => Shape::Point,
+        (_, Some(polyline), _) => Shape::Polyline(points(polyline)),
+        (_, _, Some(polygon)) => Shape::Polygon(points(polygon)),
+        _ => Shape::Other,
+    };
+
+    let properties = object
+        .properties
+        .into_iter()
+        .map(|property| {
+            let value = match property.value {
+                serde_json::Value::Bool(value) => PropertyValue::Bool(value),
+                serde_json::Value::Number(number) => {
+                    PropertyValue::Number(number.as_f64().unwrap_or_default() as f32)
+                }
+                serde_json::Value::String(text) => property_value(&property.kind, &text),
+                value => PropertyValue::Text(value.to_string()),
+            };
+
+            (property.name, value)
+        })
+        .collect();
+
+    Object {
+        name: object.name,
+        kind: if object.class.is_empty() {
+            object.kind
+        } else {
+            object.class
+        },
+        position: offset + Vec2::new(object.x, object.y),
+        rotation: object.rotation,
+        shape,
+        properties,
+    }
+}
+
+/// Loads the object layers of Tiled maps, saved as either `.tmx` or `.tmj`
+pub struct TiledLoader;
+impl AssetLoader for TiledLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+        Box::pin(async move {
+            let extension = load_context
+                .path()
+                .extension()
+                .and_then(|extension| extension.to_str())
+                .unwrap_or_default();
+
+            let map = match extension {
+                "tmx" => tmx_to_map(bytes)?,
+                "tmj" => tmj_to_map(bytes)?,
+                _ => return Err(TiledError::Extension(extension.to_owned()).into()),
+            };
+
+            load_context.set_default_asset(LoadedAsset::new(map));
+
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["tmx", "tmj"]
+    }
+}
@@ -0,0 +1,320 @@
+use std::fmt;
+
+use bevy::{
+    prelude::*,
+    utils::{HashMap, HashSet},
+};
+
+use super::{map::Map, model::Model};
+
+/// Limits for [`validate_map`] and [`validate_model`]
+pub struct Validation {
+    /// Coordinates further than this from the origin are reported as out of bounds
+    pub max_coordinate: f32,
+    /// Points closer together than this count as the same point
+    pub tolerance: f32,
+}
+
+impl Default for Validation {
+    fn default() -> Self {
+        Self {
+            max_coordinate: 1000.,
+            tolerance: 1e-3,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    /// Probably a mistake, but the game copes with it
+    Warning,
+    Error,
+}
+
+pub struct Issue {
+    pub severity: Severity,
+    /// What the issue is about, like "mesh 3"
+    pub context: String,
+    pub message: String,
+}
+
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let severity = match self.severity {
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        };
+
+        write!(f, "{severity}: {}: {}", self.context, self.message)
+    }
+}
+
+#[derive(Default)]
+pub struct Report {
+    pub meshes: usize,
+    pub lines: usize,
+    pub placements: usize,
+    /// The corners of a box around all lines and placements, if there are any
+    pub bounds: Option<(Vec3, Vec3)>,
+    pub issues: Vec<Issue>,
+}
+
+impl Report {
+    pub fn worst(&self) -> Option<Severity> {
+        self.issues.iter().map(|issue| issue.severity).max()
+    }
+
+    fn include_point(&mut self, point: Vec3) {
+        if !point.is_finite() {
+            return;
+        }
+
+        self.bounds = Some(match self.bounds {
+            Some((min, max)) => (min.min(point), max.max(point)),
+            None => (point, point),
+        });
+    }
+
+    /// Adds one issue for a problem that may come up many times, listing the first few places
+    fn add_all(&mut self, severity: Severity, context: &str, problem: &str, lines: &[usize]) {
+        let Some(first) = lines.first() else {
+            return;
+        };
+
+        let examples: Vec<_> = lines.iter().take(3).map(usize::to_string).collect();
+        let message = match lines.len() {
+            1 => format!("{problem}: line {first}"),
+            count => format!(
+                "{problem}: {count} lines ({}{})",
+                examples.join(", "),
+                if count > examples.len() { ", ..." } else { "" }
+            ),
+        };
+
+        self.issues.push(Issue {
+            severity,
+            context: context.to_owned(),
+            message,
+        });
+    }
+}
+
+impl Validation {
+    /// Checks lines as they are in the world. Duplicates are looked for in `seen`, so they can be
+    /// found across meshes. Lines that aren't `collidable` get no collider, so it doesn't matter
+    /// how they are placed against the play plane.
+    fn check_lines(
+        &self,
+        context: &str,
+        lines: &[(Vec3, Vec3)],
+        collidable: bool,
+        seen: &mut HashMap<[[i64; 3]; 2], String>,
+        report: &mut Report,
+    ) {
+        let key = |point: Vec3| (point / self.tolerance).round().as_i64vec3().to_array();
+
+        let mut not_finite = vec![];
+        let mut zero_length = vec![];
+        let mut duplicates = vec![];
+        let mut shared = vec![];
+        let mut no_collider = vec![];
+        let mut off_plane = vec![];
+        let mut out_of_bounds = vec![];
+        let mut ends: HashMap<[i64; 3], (usize, usize)> = HashMap::new();
+
+        for (i, &(a, b)) in lines.iter().enumerate() {
+            report.include_point(a);
+            report.include_point(b);
+
+            if !a.is_finite() || !b.is_finite() {
+                not_finite.push(i);
+                continue;
+            }
+
+            if a.distance(b) < self.tolerance {
+                zero_length.push(i);
+                continue;
+            }
+
+            // Lines are the same whichever way around they go
+            let mut line_key = [key(a), key(b)];
+            line_key.sort();
+            match seen.get(&line_key) {
+                Some(first) if first == context => duplicates.push(i),
+                Some(_) => shared.push(i),
+                None => {
+                    seen.insert(line_key, context.to_owned());
+                }
+            }
+
+            // Colliders are built from the lines as seen from above
+            if collidable && (a.z.abs() > self.tolerance || b.z.abs() > self.tolerance) {
+                if a.truncate().distance(b.truncate()) < self.tolerance {
+                    no_collider.push(i);
+                } else {
+                    off_plane.push(i);
+                }
+            }
+
+            if a.abs().max_element() > self.max_coordinate
+                || b.abs().max_element() > self.max_coordinate
+            {
+                out_of_bounds.push(i);
+            }
+
+            for point in [a, b] {
+                ends.entry(key(point)).or_insert((0, i)).0 += 1;
+            }
+        }
+
+        // In a closed outline, every point is where one line ends and another starts
+        let mut open_ends: Vec<_> = ends
+            .into_values()
+            .filter(|(count, _)| *count == 1)
+            .map(|(_, line)| line)
+            .collect();
+        open_ends.sort();
+        open_ends.dedup();
+
+        let max = self.max_coordinate;
+        for (severity, lines, problem) in [
+            (Severity::Error, not_finite, "coordinates that aren't numbers"),
+            (Severity::Error, zero_length, "no length"),
+            (Severity::Error, duplicates, "duplicate"),
+            (Severity::Error, shared, "duplicate of a line in an earlier mesh"),
+            (Severity::Error, no_collider, "upright, so no collider"),
+            (Severity::Error, out_of_bounds, &format!("further than {max} from the origin")),
+            (Severity::Warning, off_plane, "off the Z = 0 plane, but colliding on it"),
+            (Severity::Warning, open_ends, "open end, so the outline isn't closed"),
+        ] {
+            report.add_all(severity, context, problem, &lines);
+        }
+    }
+
+    /// The checks that make sense for a model on its own. Where its lines end up, and whether
+    /// they close an outline, depends on the walls it is included in.
+    fn check_model_lines(&self, lines: &[(Vec3, Vec3)], report: &mut Report) {
+        let key = |point: Vec3| (point / self.tolerance).round().as_i64vec3().to_array();
+
+        let mut not_finite = vec![];
+        let mut zero_length = vec![];
+        let mut duplicates = vec![];
+        let mut seen = HashSet::new();
+
+        for (i, &(a, b)) in lines.iter().enumerate() {
+            report.include_point(a);
+            report.include_point(b);
+
+            if !a.is_finite() || !b.is_finite() {
+                not_finite.push(i);
+                continue;
+            }
+
+            if a.distance(b) < self.tolerance {
+                zero_length.push(i);
+                continue;
+            }
+
+            let mut line_key = [key(a), key(b)];
+            line_key.sort();
+            if !seen.insert(line_key) {
+                duplicates.push(i);
+            }
+        }
+
+        for (lines, problem) in [
+            (not_finite, "coordinates that aren't numbers"),
+            (zero_length, "no length"),
+            (duplicates, "duplicate"),
+        ] {
+            report.add_all(Severity::Error, "model", problem, &lines);
+        }
+    }
+
+    fn check_transform(&self, context: &str, transform: &Transform, report: &mut Report) {
+        let finite = transform.translation.is_finite()
+            && transform.rotation.is_finite()
+            && transform.scale.is_finite();
+
+        let problem = if !finite {
+            "transform values that aren't numbers"
+        } else if transform.scale.cmpeq(Vec3::ZERO).any() {
+            "scaled to nothing"
+        } else {
+            return;
+        };
+
+        report.issues.push(Issue {
+            severity: Severity::Error,
+            context: context.to_owned(),
+            message: problem.to_owned(),
+        });
+    }
+}
+
+/// Looks for problems in a map, as it is after loading: with its primitives expanded, but without
+/// the lines of included models
+pub fn validate_map(map: &Map, validation: &Validation) -> Report {
+    let mut report = Report {
+        meshes: map.map_meshes.len(),
+        placements: map.placements.len(),
+        ..default()
+    };
+
+    let mut seen = HashMap::new();
+
+    for (i, map_mesh) in map.map_meshes.iter().enumerate() {
+        let context = format!("mesh {i}");
+        validation.check_transform(&context, &map_mesh.transform, &mut report);
+
+        let matrix = map_mesh.transform.compute_affine();
+        let lines: Vec<_> = map_mesh
+            .lines
+            .iter()
+            .map(|(a, b)| (matrix.transform_point3(*a), matrix.transform_point3(*b)))
+            .collect();
+
+        report.lines += lines.len();
+        let collidable = map_mesh.surface.collidable;
+        validation.check_lines(&context, &lines, collidable, &mut seen, &mut report);
+    }
+
+    for (i, placement) in map.placements.iter().enumerate() {
+        let context = format!("placement {i} ({:?})", placement.kind);
+        validation.check_transform(&context, &placement.transform, &mut report);
+
+        let position = placement.transform.translation;
+        report.include_point(position);
+
+        if position.abs().max_element() > validation.max_coordinate {
+            report.issues.push(Issue {
+                severity: Severity::Error,
+                context,
+                message: format!("further than {} from the origin", validation.max_coordinate),
+            });
+        }
+    }
+
+    report
+}
+
+/// Looks for problems in a model, not counting the lines of included models
+pub fn validate_model(model: &Model, validation: &Validation) -> Report {
+    let mut report = Report { meshes: 1, ..default() };
+
+    if let Err(err) = model.validate() {
+        report.issues.push(Issue {
+            severity: Severity::Error,
+            context: "model".to_owned(),
+            message: err.to_string(),
+        });
+
+        return report;
+    }
+
+    let lines = model.lines();
+    report.lines = lines.len();
+    validation.check_model_lines(&lines, &mut report);
+
+    report
+}
@@ -2,7 +2,7 @@ use bevy::prelude::*;
 use bevy_hanabi::prelude::*;
 use bevy_rapier3d::prelude::*;
 
-use crate::{collision_groups, damageable::Damageable, team::Team};
+use crate::{collision_groups, damageable::Damageable, surface::Surface, team::Team};
 
 pub struct BulletPlugin;
 
@@ -82,8 +82,9 @@ fn setup_particle_effect(mut effects: ResMut<Assets<EffectAsset>>, mut commands:
 }
 
 fn collide_bullets(
-    query: Query<(Entity, &Bullet, &Transform, &Velocity, Option<&Team>)>,
+    mut query: Query<(Entity, &Bullet, &mut Transform, &mut Velocity, Option<&Team>)>,
     team_targets: Query<&Team>,
+    surfaces: Query<&Surface>,
     mut damage_targets: Query<&mut Damageable>,
     mut velocity_targets: Query<(&GlobalTransform, &mut ExternalImpulse)>,
     context: Res<RapierContext>,
@@ -93,7 +94,7 @@ fn collide_bullets(
         (With<BulletImpactEffect>, Without<Bullet>),
     >,
 ) {
-    for (bullet_entity, bullet, transform, velocity, bullet_team) in query.iter() {
+    for (bullet_entity, bullet, mut transform, mut velocity, bullet_team) in query.iter_mut() {
         let dir = velocity.linvel.normalize_or_zero();
 
         let Some((target_entity, intersection)) = context.cast_ray_and_get_normal(
@@ -125,6 +126,27 @@ fn collide_bullets(
         spawner.reset();
         println!("spawning");
 
+        if surfaces
+            .get(target_entity)
+            .map_or(false, |surface| surface.reflects_bullets)
+        {
+            let normal = if intersection.normal.dot(dir) > 0. {
+                -intersection.normal
+            } else {
+                intersection.normal
+            };
+
+            let linvel = velocity.linvel;
+            velocity.linvel = linvel - 2. * linvel.dot(normal) * normal;
+
+            // Move the bullet far enough off the wall that next frame's ray starts in front of it
+            let dir = velocity.linvel.normalize_or_zero();
+            transform.translation = intersection.point + dir * 0.6;
+            transform.rotation = Quat::from_rotation_arc(Vec3::X, dir);
+
+            continue;
+        }
+
         commands.entity(bullet_entity).despawn();
 
         if let Ok(mut damageable) = damage_targets.get_mut(target_entity) {
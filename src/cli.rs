@@ -0,0 +1,212 @@
+//! Commands that run instead of the game, like `emitter check assets/maps/world.map.ron`
+
+use std::path::Path;
+
+use crate::assets::{
+    map::Map,
+    model::Model,
+    obj::obj_to_model,
+    svg::SvgImport,
+    thumbnail::{Thumbnail, View},
+    validation::{validate_map, validate_model, Severity, Validation},
+};
+
+/// Runs the command named by the first argument, if there is one, returning the exit status
+pub fn run(args: &[String]) -> Option<i32> {
+    let (command, args) = args.split_first()?;
+
+    match command.as_str() {
+        "check" => Some(check(args)),
+        "thumbnail" => Some(thumbnail(args)),
+        _ => None,
+    }
+}
+
+const CHECK_USAGE: &str = "usage: emitter check [--strict] [--max-coordinate N] FILE...
+
+Checks maps and models for problems and prints their statistics. Exits
+with 1 if any file has errors, or warnings too with --strict.";
+
+fn check(args: &[String]) -> i32 {
+    let mut validation = Validation::default();
+    let mut strict = false;
+    let mut files = vec![];
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--strict" => strict = true,
+            "--max-coordinate" => match args.next().and_then(|value| value.parse().ok()) {
+                Some(max) => validation.max_coordinate = max,
+                None => {
+                    eprintln!("{CHECK_USAGE}");
+                    return 2;
+                }
+            },
+            flag if flag.starts_with('-') => {
+                eprintln!("{CHECK_USAGE}");
+                return 2;
+            }
+            _ => files.push(Path::new(arg)),
+        }
+    }
+
+    if files.is_empty() {
+        eprintln!("{CHECK_USAGE}");
+        return 2;
+    }
+
+    let mut failed = false;
+
+    for path in files {
+        println!("{}", path.display());
+
+        let report = match load(path) {
+            Ok(Asset::Map(map)) => validate_map(&map, &validation),
+            Ok(Asset::Model(model)) => validate_model(&model, &validation),
+            Err(err) => {
+                println!("  error: {err}");
+                failed = true;
+                continue;
+            }
+        };
+
+        println!(
+            "  {} meshes, {} lines, {} placements",
+            report.meshes, report.lines, report.placements
+        );
+
+        if let Some((min, max)) = report.bounds {
+            println!("  bounds {min} to {max}");
+        }
+
+        for issue in &report.issues {
+            println!("  {issue}");
+        }
+
+        failed |= match report.worst() {
+            Some(Severity::Error) => true,
+            Some(Severity::Warning) => strict,
+            None => false,
+        };
+    }
+
+    i32::from(failed)
+}
+
+const THUMBNAIL_USAGE: &str = "usage: emitter thumbnail [--size WIDTHxHEIGHT] [--view VIEW]
+       [--bloom AMOUNT] [--no-placements] INPUT OUTPUT.png
+
+Draws a map or model into an image. VIEW is top, front, side, iso, or YAW,PITCH in degrees.";
+
+fn thumbnail(args: &[String]) -> i32 {
+    let mut thumbnail = Thumbnail::default();
+    let mut files = vec![];
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let parsed = match arg.as_str() {
+            "--size" => args
+                .next()
+                .and_then(|size| size.split_once('x'))
+                .and_then(|(width, height)| Some((width.parse().ok()?, height.parse().ok()?)))
+                .map(|(width, height)| {
+                    thumbnail.width = width;
+                    thumbnail.height = height;
+                }),
+            "--view" => args.next().and_then(|view| parse_view(view)).map(|view| {
+                thumbnail.view = view;
+            }),
+            "--bloom" => args
+                .next()
+                .and_then(|bloom| bloom.parse().ok())
+                .map(|bloom| thumbnail.bloom = bloom),
+            "--no-placements" => {
+                thumbnail.placements = false;
+                Some(())
+            }
+            flag if flag.starts_with('-') => None,
+            _ => {
+                files.push(Path::new(arg));
+                Some(())
+            }
+        };
+
+        if parsed.is_none() {
+            eprintln!("{THUMBNAIL_USAGE}");
+            return 2;
+        }
+    }
+
+    let [input, output] = files[..] else {
+        eprintln!("{THUMBNAIL_USAGE}");
+        return 2;
+    };
+
+    let image = match load(input) {
+        Ok(Asset::Map(map)) => thumbnail.map(&map),
+        Ok(Asset::Model(model)) => thumbnail.model(&model),
+        Err(err) => {
+            eprintln!("{}: {err}", input.display());
+            return 1;
+        }
+    };
+
+    if let Err(err) = image.save_with_format(output, image::ImageFormat::Png) {
+        eprintln!("{}: {err}", output.display());
+        return 1;
+    }
+
+    0
+}
+
+fn parse_view(view: &str) -> Option<View> {
+    match view {
+        "top" => Some(View::TOP),
+        "front" => Some(View::FRONT),
+        "side" => Some(View::SIDE),
+        "iso" => Some(View::ISOMETRIC),
+        angles => {
+            let (yaw, pitch) = angles.split_once(',')?;
+            Some(View {
+                yaw: yaw.trim().parse().ok()?,
+                pitch: pitch.trim().parse().ok()?,
+            })
+        }
+    }
+}
+
+enum Asset {
+    Map(Map),
+    Model(Model),
+}
+
+/// Reads a map or model, in any of the formats the game loads them from
+fn load(path: &Path) -> Result<Asset, String> {
+    let bytes = std::fs::read(path).map_err(|err| err.to_string())?;
+    let name = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or_default();
+
+    if name.ends_with(".map.ron") || name.ends_with(".map.bin") {
+        Map::from_bytes(&bytes)
+            .map(Asset::Map)
+            .map_err(|err| err.to_string())
+    } else if name.ends_with(".mdl.ron") {
+        ron::de::from_bytes(&bytes)
+            .map(Asset::Model)
+            .map_err(|err| err.to_string())
+    } else if name.ends_with(".svg") {
+        SvgImport::default()
+            .model(&bytes)
+            .map(Asset::Model)
+            .map_err(|err| err.to_string())
+    } else if name.ends_with(".obj") {
+        obj_to_model(&bytes)
+            .map(Asset::Model)
+            .map_err(|err| err.to_string())
+    } else {
+        Err("not a map or model".to_owned())
+    }
+}
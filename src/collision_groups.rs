@@ -5,6 +5,7 @@ pub const ENEMY: Group = Group::GROUP_2;
 pub const BULLET: Group = Group::GROUP_3;
 pub const WALL: Group = Group::GROUP_4;
 pub const EDITOR_HANDLE: Group = Group::GROUP_5;
+pub const PICKUP: Group = Group::GROUP_6;
 
 pub const ALL: Group = Group::ALL;
 pub const NONE: Group = Group::NONE;
@@ -10,7 +10,7 @@ pub struct Damageable {
 pub fn despawn_if_dead(mut commands: Commands, query: Query<(Entity, &Damageable)>) {
     for (entity, damageable) in query.iter() {
         if damageable.health <= 0. {
-            commands.entity(entity).despawn();
+            commands.entity(entity).despawn_recursive();
         }
     }
 }
@@ -0,0 +1,125 @@
+use std::{
+    fs::File,
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use bevy::{asset::HandleId, prelude::*, tasks::IoTaskPool};
+
+use super::scene::{MapContents, ModelFormat};
+use crate::assets::{
+    map::{convert_map, MapEncoding},
+    model::Model,
+    svg::map_to_svg,
+};
+
+/// Writes the map as it is in the editor to `assets/maps/world.svg`, for viewing in a browser
+#[derive(Event)]
+pub struct ExportSvg;
+
+pub fn export_svg(mut events: EventReader<ExportSvg>, contents: MapContents) {
+    if events.iter().next().is_none() {
+        return;
+    }
+
+    let svg = map_to_svg(&contents.to_map());
+
+    IoTaskPool::get()
+        .spawn(async move {
+            File::create("assets/maps/world.svg")
+                .and_then(|mut file| file.write_all(svg.as_bytes()))
+                .expect("Error while writing SVG to file");
+        })
+        .detach();
+}
+
+/// Writes a loaded model next to the file it was loaded from, in another format
+#[derive(Event)]
+pub struct ExportModel {
+    pub model: HandleId,
+    pub format: ModelFormat,
+}
+
+pub fn export_models(
+    mut events: EventReader<ExportModel>,
+    models: Res<Assets<Model>>,
+    asset_server: Res<AssetServer>,
+) {
+    for ExportModel { model, format } in events.iter() {
+        let (Some(source), Some(model)) =
+            (asset_server.get_handle_path(*model), models.get(&Handle::weak(*model)))
+        else {
+            continue;
+        };
+
+        let source = Path::new("assets").join(source.path());
+        let Some(stem) = source
+            .file_name()
+            .and_then(|name| name.to_str())
+            .and_then(|name| name.split('.').next())
+        else {
+            continue;
+        };
+
+        let path = source.with_file_name(format!("{stem}.{}", format.extension()));
+        if path == source {
+            warn!("Not exporting {source:?} over itself");
+            continue;
+        }
+
+        let contents = format.write(model);
+
+        IoTaskPool::get()
+            .spawn(async move {
+                File::create(&path)
+                    .and_then(|mut file| file.write_all(contents.as_bytes()))
+                    .expect("Error while writing model to file");
+            })
+            .detach();
+    }
+}
+
+/// Rewrites a map file next to itself in the other encoding, e.g. `world.map.ron` to
+/// `world.map.bin`
+#[derive(Event)]
+pub struct ConvertMap {
+    pub path: PathBuf,
+}
+
+pub fn convert_maps(mut events: EventReader<ConvertMap>) {
+    for ConvertMap { path } in events.iter() {
+        let file_name = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .unwrap_or_default();
+
+        let Some((stem, encoding)) = [MapEncoding::Ron, MapEncoding::Binary]
+            .into_iter()
+            .find_map(|from| {
+                let stem = file_name.strip_suffix(from.extension())?;
+                let to = match from {
+                    MapEncoding::Ron => MapEncoding::Binary,
+                    MapEncoding::Binary => MapEncoding::Ron,
+                };
+                Some((stem, to))
+            })
+        else {
+            error!("Not converting {path:?}: maps end in .map.ron or .map.bin");
+            continue;
+        };
+
+        let converted = std::fs::read(path)
+            .map_err(|err| err.to_string())
+            .and_then(|bytes| convert_map(&bytes, encoding).map_err(|err| err.to_string()));
+
+        let target = path.with_file_name(format!("{stem}{}", encoding.extension()));
+        match converted {
+            Ok(bytes) => {
+                if let Err(err) = std::fs::write(&target, bytes) {
+                    error!("Could not write {target:?}: {err}");
+                }
+            }
+            Err(err) => error!("Could not convert {path:?}: {err}"),
+        }
+    }
+}
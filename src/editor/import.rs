@@ -0,0 +1,88 @@
+use std::path::PathBuf;
+
+use bevy::prelude::*;
+
+use super::{
+    mesh::{WallBundle, WallColors},
+    ui::UiState,
+};
+use crate::{
+    assets::{dxf::DxfImport, svg::SvgImport},
+    line_material::ColorSpec,
+    surface::Surface,
+};
+
+/// Reads a file made in another tool and adds its shapes to the map as walls, which get colliders
+/// like any other wall. The kind of file is picked by its extension.
+#[derive(Event)]
+pub struct ImportFile {
+    pub path: PathBuf,
+}
+
+pub fn import_files(
+    mut events: EventReader<ImportFile>,
+    ui_state: Res<UiState>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut commands: Commands,
+) {
+    for ImportFile { path } in events.iter() {
+        let bytes = match std::fs::read(path) {
+            Ok(bytes) => bytes,
+            Err(err) => {
+                error!("Could not read {path:?}: {err}");
+                continue;
+            }
+        };
+
+        let extension = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .unwrap_or_default();
+
+        let walls = match extension {
+            "svg" => SvgImport::default()
+                .line_lists(&bytes)
+                .map(|line_lists| {
+                    line_lists
+                        .into_iter()
+                        .map(|line_list| (line_list.lines, ColorSpec::Uniform(line_list.color)))
+                        .collect()
+                })
+                .map_err(|err| err.to_string()),
+            // One wall per layer
+            "dxf" => DxfImport::default()
+                .map_meshes(&bytes)
+                .map(|map_meshes| {
+                    map_meshes
+                        .into_iter()
+                        .map(|map_mesh| (map_mesh.lines, map_mesh.colors))
+                        .collect()
+                })
+                .map_err(|err| err.to_string()),
+            _ => Err(format!("don't know how to import .{extension} files")),
+        };
+
+        let walls: Vec<_> = match walls {
+            Ok(walls) => walls,
+            Err(err) => {
+                error!("Could not import {path:?}: {err}");
+                continue;
+            }
+        };
+
+        for (lines, colors) in walls {
+            let colors = WallColors {
+                colors,
+                intensity: ui_state.new_mesh_props.intensity,
+            };
+
+            commands.spawn(WallBundle::new(
+                lines,
+                colors,
+                Surface::default(),
+                Transform::default(),
+                &mut meshes,
+            ));
+        }
+    }
+}
@@ -9,7 +9,9 @@ use super::{
 };
 use crate::{
     collision_groups,
-    line_material::{LineList, LineMaterial},
+    enemy::ContactDamage,
+    line_material::{ColorSpec, LineList, LineMaterial},
+    surface::Surface,
 };
 
 #[derive(Component)]
@@ -57,7 +59,7 @@ impl MeshPointBundle {
             //     material: materials.add(LineMaterial { color: Color::PURPLE }),
             //     ..default()
             // },
-            mesh: asset_server.load("models/editor-handle.mdl.ron"),
+            mesh: asset_server.load("models/editor-handle.mdl.ron#mesh"),
             material: Default::default(),
             transform: Default::default(),
             global_transform: Default::default(),
@@ -294,6 +296,142 @@ pub struct Solidify;
 #[derive(Component)]
 pub struct WallMesh;
 
+/// A wall as it is spawned from a map or the editor. Physics are added by `update_wall_colliders`
+/// once the wall exists.
+#[derive(Bundle)]
+pub struct WallBundle {
+    pub mesh: MaterialMeshBundle<StandardMaterial>,
+    pub colors: WallColors,
+    pub surface: Surface,
+    pub wall_mesh: WallMesh,
+}
+
+impl WallBundle {
+    pub fn new(
+        lines: Vec<(Vec3, Vec3)>,
+        colors: WallColors,
+        surface: Surface,
+        transform: Transform,
+        meshes: &mut Assets<Mesh>,
+    ) -> Self {
+        Self {
+            mesh: MaterialMeshBundle {
+                mesh: meshes.add(colors.mesh(lines)),
+                transform,
+                ..default()
+            },
+            colors,
+            surface,
+            wall_mesh: WallMesh,
+        }
+    }
+}
+
+/// The colors a wall was built from. The mesh only has the end result, with the intensity
+/// already multiplied in, so this is what gets saved.
+#[derive(Component, Reflect, Clone)]
+#[reflect(Component)]
+pub struct WallColors {
+    pub colors: ColorSpec,
+    pub intensity: f32,
+}
+
+impl Default for WallColors {
+    fn default() -> Self {
+        Self {
+            colors: ColorSpec::default(),
+            intensity: 1.,
+        }
+    }
+}
+
+impl WallColors {
+    pub fn vertex_colors(&self, line_count: usize) -> Vec<[f32; 4]> {
+        self.colors
+            .vertex_colors(line_count)
+            .into_iter()
+            .map(|color| (color * self.intensity).as_linear_rgba_f32())
+            .collect()
+    }
+
+    pub fn mesh(&self, lines: Vec<(Vec3, Vec3)>) -> Mesh {
+        let line_count = lines.len();
+        let mut mesh = Mesh::from(LineList { lines, color: Color::WHITE });
+        mesh.insert_attribute(Mesh::ATTRIBUTE_COLOR, self.vertex_colors(line_count));
+        mesh
+    }
+}
+
+/// The lines a wall mesh was built from, in the wall's own space
+pub fn wall_lines(mesh: &Mesh) -> Option<Vec<(Vec3, Vec3)>> {
+    let positions = mesh.attribute(Mesh::ATTRIBUTE_POSITION)?.as_float3()?;
+
+    Some(
+        positions
+            .iter()
+            .tuples()
+            .map(|(a, b)| (Vec3::from(*a), Vec3::from(*b)))
+            .collect(),
+    )
+}
+
+/// Rebuilds a wall's physics whenever it moves or its surface changes
+pub fn update_wall_colliders(
+    walls: Query<
+        (Entity, &Transform, &Handle<Mesh>, &Surface),
+        (With<WallMesh>, Or<(Changed<Transform>, Changed<Surface>)>),
+    >,
+    meshes: Res<Assets<Mesh>>,
+    mut commands: Commands,
+) {
+    for (entity, transform, mesh, surface) in walls.iter() {
+        let Some(lines) = meshes.get(mesh).and_then(wall_lines) else {
+            continue;
+        };
+
+        let mut wall = commands.entity(entity);
+        wall.remove::<(
+            RigidBody,
+            Collider,
+            ColliderScale,
+            CollisionGroups,
+            Restitution,
+            ContactDamage,
+        )>();
+
+        if surface.collidable {
+            if let Some(collider) = surface.collider(&lines, transform) {
+                wall.insert((
+                    RigidBody::Fixed,
+                    collider,
+                    // The collider already has the scale baked in
+                    ColliderScale::Absolute(Vec3::ONE),
+                    surface.collision_groups(),
+                    Restitution::coefficient(surface.restitution),
+                ));
+            }
+        }
+
+        if surface.contact_damage > 0. {
+            wall.insert(ContactDamage { damage: surface.contact_damage });
+        }
+    }
+}
+
+pub fn apply_wall_colors(
+    walls: Query<(&WallColors, &Handle<Mesh>), Changed<WallColors>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+) {
+    for (colors, mesh) in walls.iter() {
+        let Some(mesh) = meshes.get_mut(mesh) else {
+            continue;
+        };
+
+        let line_count = mesh.count_vertices() / 2;
+        mesh.insert_attribute(Mesh::ATTRIBUTE_COLOR, colors.vertex_colors(line_count));
+    }
+}
+
 pub fn solidify(
     mut event_reader: EventReader<Solidify>,
     point_query: Query<(Entity, &Transform, &MeshPoint)>,
@@ -307,8 +445,6 @@ pub fn solidify(
     }
 
     let mut lines = vec![];
-    let mut collider_vertices = vec![];
-    let mut collider_indices = vec![];
 
     for (entity, mesh_line) in line_query.iter() {
         let from = point_query.get(mesh_line.start).unwrap();
@@ -316,20 +452,6 @@ pub fn solidify(
 
         lines.push((from.1.translation, to.1.translation));
 
-        if from.1.translation.z == 0.0 && to.1.translation.z == 0.0 {
-            let idx = collider_vertices.len() as u32;
-
-            collider_vertices.extend_from_slice(&[
-                from.1.translation - Vec3::Z,
-                from.1.translation + Vec3::Z,
-                to.1.translation - Vec3::Z,
-                to.1.translation + Vec3::Z,
-            ]);
-
-            collider_indices.push([idx, idx + 2, idx + 1]);
-            collider_indices.push([idx + 1, idx + 2, idx + 3]);
-        }
-
         commands.entity(entity).despawn_recursive();
     }
 
@@ -337,21 +459,17 @@ pub fn solidify(
         commands.entity(entity).despawn_recursive();
     }
 
-    commands.spawn((
-        MaterialMeshBundle::<StandardMaterial> {
-            mesh: meshes.add(
-                LineList {
-                    lines,
-                    color: ui_state.new_mesh_props.color(),
-                }
-                .into(),
-            ),
-            ..default()
-        },
-        RigidBody::Fixed,
-        CollisionGroups::new(collision_groups::WALL, collision_groups::ALL),
-        Collider::trimesh(collider_vertices, collider_indices),
-        WallMesh,
+    let colors = WallColors {
+        colors: ColorSpec::Uniform(ui_state.new_mesh_props.color),
+        intensity: ui_state.new_mesh_props.intensity,
+    };
+
+    commands.spawn(WallBundle::new(
+        lines,
+        colors,
+        Surface::default(),
+        Transform::default(),
+        &mut meshes,
     ));
 }
 
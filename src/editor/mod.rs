@@ -13,17 +13,22 @@ use bevy_rapier3d::prelude::*;
 use leafwing_input_manager::{prelude::*, InputManagerBundle};
 
 use self::{
+    export::*,
     hover_effect::*,
+    import::*,
     input::*,
     mesh::*,
     ui::{InspectorSelection, UiState},
 };
-use crate::line_material::LineMaterial;
+use crate::line_material::{ColorSpec, LineMaterial};
 
+pub mod export;
 pub mod hover_effect;
+pub mod import;
 pub mod input;
 pub mod mesh;
 pub mod scene;
+pub mod streaming;
 pub mod ui;
 
 pub struct EditorPlugin;
@@ -37,6 +42,8 @@ impl Plugin for EditorPlugin {
             .init_resource::<ui::UiState>()
             .init_resource::<CursorHoveringEntity>()
             .register_type::<CursorHoveringEntity>()
+            .register_type::<WallColors>()
+            .register_type::<ColorSpec>()
             .register_asset_reflect::<LineMaterial>()
             .add_startup_system(spawn_window)
             .add_startup_system(setup_effect)
@@ -50,9 +57,15 @@ impl Plugin for EditorPlugin {
             .add_system(spawn_line)
             .add_system(update_lines)
             .add_system(explode_mesh)
+            .add_systems(Update, (apply_wall_colors, update_wall_colliders))
             .add_event::<Solidify>()
             .add_event::<DeleteConnectedLines>()
             .add_event::<ExplodeMesh>()
+            .add_event::<ImportFile>()
+            .add_event::<ExportSvg>()
+            .add_event::<ExportModel>()
+            .add_event::<ConvertMap>()
+            .add_systems(Update, (import_files, export_svg, export_models, convert_maps))
             .add_system(solidify)
             .add_system(delete_connected_lines);
     }
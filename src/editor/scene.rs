@@ -1,24 +1,174 @@
-use std::{fs::File, io::Write, path::Path};
+use std::{
+    ffi::OsStr,
+    fs::File,
+    io::Write,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
 
-use bevy::{prelude::*, render::mesh::VertexAttributeValues, tasks::IoTaskPool};
-use bevy_rapier3d::prelude::*;
-use itertools::Itertools;
+use bevy::{
+    asset::{HandleId, LoadState},
+    ecs::system::SystemParam,
+    prelude::*,
+    tasks::IoTaskPool,
+};
+use bevy_rapier3d::prelude::ColliderDisabled;
 
-use super::mesh::WallMesh;
+use super::{
+    mesh::{wall_lines, WallBundle, WallColors, WallMesh},
+    streaming::{stream_chunks, wall_bounds, MapChunks, StreamingSettings},
+};
 use crate::{
-    assets::map::{Map, MapMesh},
-    line_material::{LineList, LineMaterial},
+    assets::{
+        map::{
+            generator::MapGenerator, Map, MapEncoding, MapMesh, MapMeshSource, Placement,
+            PlacementKind,
+        },
+        model::Model,
+        obj::model_to_obj,
+        svg::model_to_svg,
+    },
+    enemy::archetype::EnemySpawnToken,
+    line_material::ColorSpec,
+    pickup::Pickup,
+    player::PlayerStart,
+    surface::{Surface, WallShape},
 };
 
 pub struct ScenePlugin;
 impl Plugin for ScenePlugin {
     fn build(&self, app: &mut App) {
-        app.add_event::<SaveScene>().add_system(save_scene);
+        app.register_type::<Surface>()
+            .register_type::<WallShape>()
+            .init_resource::<StreamingSettings>()
+            .register_type::<StreamingSettings>()
+            .init_resource::<MapGenerator>()
+            .register_type::<MapGenerator>()
+            .add_event::<GenerateMap>()
+            .add_event::<SaveScene>()
+            .add_event::<SaveModel>()
+            .add_system(save_scene)
+            .add_systems(Update, save_model)
+            .add_systems(Update, generate_map.before(spawn_current_map))
+            .add_systems(Update, (spawn_current_map, report_map_load_state))
+            .add_systems(Update, stream_chunks.after(spawn_current_map));
+    }
+}
+
+/// The map being played. Its walls are grouped into [`MapChunks`] again whenever the asset is
+/// (re)loaded.
+#[derive(Resource)]
+pub struct CurrentMap {
+    pub handle: Handle<Map>,
+    /// Where the map was loaded from, relative to `assets`
+    pub path: String,
+    pub load_state: LoadState,
+}
+
+impl CurrentMap {
+    pub fn load(path: String, asset_server: &AssetServer) -> Self {
+        Self {
+            handle: asset_server.load(&path),
+            path,
+            load_state: LoadState::NotLoaded,
+        }
+    }
+
+    /// Where the map is saved in `encoding`: next to where it was loaded from, under the same
+    /// name. Maps imported from other formats are saved as maps of their own.
+    pub fn save_path(&self, encoding: MapEncoding) -> PathBuf {
+        let path = Path::new("assets").join(&self.path);
+        let name = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .unwrap_or_default();
+        let stem = name.split('.').next().unwrap_or(name);
+
+        path.with_file_name(format!("{stem}.{}", encoding.extension()))
+    }
+}
+
+/// The map opened when no other is asked for: `maps/world.map.bin` if it was saved more recently
+/// than `maps/world.map.ron`, so whichever encoding was saved last is the one that is loaded
+pub fn default_map_path() -> String {
+    let modified = |path| {
+        std::fs::metadata(Path::new("assets").join(path))
+            .and_then(|metadata| metadata.modified())
+            .ok()
+    };
+
+    let (ron, bin) = ("maps/world.map.ron", "maps/world.map.bin");
+    match (modified(ron), modified(bin)) {
+        (Some(ron_modified), Some(bin_modified)) if bin_modified > ron_modified => bin,
+        (None, Some(_)) => bin,
+        _ => ron,
+    }
+    .to_owned()
+}
+
+/// Saves the map as it is in the editor, next to the current map under the same name, as
+/// `.map.ron` or `.map.bin`
+#[derive(Event)]
+pub struct SaveScene {
+    pub encoding: MapEncoding,
+}
+
+/// Saves the given walls as a model in `assets/models`, relative to the first wall
+#[derive(Event)]
+pub struct SaveModel {
+    pub name: String,
+    pub walls: Vec<Entity>,
+    pub format: ModelFormat,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ModelFormat {
+    Ron,
+    Svg,
+    Obj,
+}
+
+impl ModelFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            ModelFormat::Ron => "mdl.ron",
+            ModelFormat::Svg => "svg",
+            ModelFormat::Obj => "obj",
+        }
+    }
+
+    pub fn write(self, model: &Model) -> String {
+        match self {
+            ModelFormat::Ron => {
+                ron::ser::to_string_pretty(model, ron::ser::PrettyConfig::default()).unwrap()
+            }
+            ModelFormat::Svg => model_to_svg(model),
+            ModelFormat::Obj => model_to_obj(model),
+        }
     }
 }
 
+/// Generates a map with the current [`MapGenerator`] settings, writes it to
+/// `assets/maps/generated.map.ron` and opens it in place of the current map. Saving it writes it
+/// back there, like any other map.
 #[derive(Event)]
-pub struct SaveScene;
+pub struct GenerateMap;
+
+/// One of the map's placements, kept so it can be saved back out. Enemies and pickups are spawned
+/// as [`MapSpawned`] entities of their own, so the placement outlives them.
+#[derive(Component)]
+pub struct MapPlacement(pub Placement);
+
+/// Gameplay entities spawned for the map's placements, which are despawned along with the map's
+/// own entities when it is spawned again
+#[derive(Component)]
+pub struct MapSpawned;
+
+/// A named point placed by the map
+#[derive(Component)]
+pub struct MapMarker {
+    pub name: String,
+}
 
 // #[derive(Reflect, Component, Default)]
 // #[reflect(Component)]
@@ -78,105 +228,387 @@ pub struct SaveScene;
 //         .detach();
 // }
 
+/// The walls and placements in the world, as they would be saved
+#[derive(SystemParam)]
+pub struct MapContents<'w, 's> {
+    walls: Query<
+        'w,
+        's,
+        (
+            &'static Transform,
+            &'static Handle<Mesh>,
+            Option<&'static WallColors>,
+            Option<&'static Surface>,
+            Option<&'static MapMeshSource>,
+        ),
+        With<WallMesh>,
+    >,
+    placements: Query<'w, 's, &'static MapPlacement>,
+    meshes: Res<'w, Assets<Mesh>>,
+}
+
+impl MapContents<'_, '_> {
+    pub fn to_map(&self) -> Map {
+        let mut map_meshes = vec![];
+
+        for (transform, mesh, colors, surface, source) in self.walls.iter() {
+            let Some(lines) = self.meshes.get(mesh).and_then(wall_lines) else {
+                continue;
+            };
+
+            let WallColors { colors, intensity } = colors.cloned().unwrap_or_default();
+
+            let mut map_mesh = MapMesh {
+                transform: *transform,
+                lines,
+                primitives: vec![],
+                includes: vec![],
+                colors,
+                intensity,
+                surface: surface.cloned().unwrap_or_default(),
+                source: None,
+            };
+
+            // Walls loaded from the map keep their primitives and includes, rather than being
+            // saved as the lines they expand to
+            if let Some(source) =
+                source.filter(|source| source.expanded_lines == map_mesh.lines.len())
+            {
+                let vertex_colors = map_mesh.colors.vertex_colors(source.expanded_lines);
+
+                map_mesh.lines.truncate(source.lines);
+                map_mesh.primitives = source.primitives.clone();
+                map_mesh.includes = source.includes.clone();
+                map_mesh.colors =
+                    ColorSpec::from_vertex_colors(&vertex_colors[..source.colored_lines * 2]);
+            }
+
+            map_meshes.push(map_mesh);
+        }
+
+        let placements = self
+            .placements
+            .iter()
+            .map(|MapPlacement(placement)| placement.clone())
+            .collect();
+
+        Map::new(map_meshes, placements)
+    }
+}
+
 pub fn save_scene(
-    mut event: EventReader<SaveScene>,
-    walls: Query<(&Transform, &Handle<LineMaterial>, &Handle<Mesh>), With<WallMesh>>,
-    materials: Res<Assets<LineMaterial>>,
+    mut events: EventReader<SaveScene>,
+    current_map: Option<Res<CurrentMap>>,
+    contents: MapContents,
+) {
+    for SaveScene { encoding } in events.iter() {
+        let path = current_map.as_ref().map_or_else(
+            || Path::new("assets/maps/world").with_extension(encoding.extension()),
+            |current_map| current_map.save_path(*encoding),
+        );
+        let bytes = contents.to_map().to_bytes(*encoding);
+
+        IoTaskPool::get()
+            .spawn(async move {
+                File::create(path)
+                    .and_then(|mut file| file.write_all(&bytes))
+                    .expect("Error while writing scene to file");
+            })
+            .detach();
+    }
+}
+
+pub fn save_model(
+    mut events: EventReader<SaveModel>,
+    walls: Query<(&Transform, &Handle<Mesh>, Option<&WallColors>), With<WallMesh>>,
     meshes: Res<Assets<Mesh>>,
 ) {
-    if event.iter().next().is_none() {
+    for SaveModel { name, walls: entities, format } in events.iter() {
+        if !is_file_stem(name) {
+            warn!("Not saving model {name:?}: the name has to be a file name, without a folder");
+            continue;
+        }
+
+        let mut lines = vec![];
+        let mut colors = vec![];
+        let mut origin = None;
+
+        for (transform, mesh, wall_colors) in walls.iter_many(entities) {
+            let Some(wall) = meshes.get(mesh).and_then(wall_lines) else {
+                continue;
+            };
+
+            let origin = *origin.get_or_insert_with(|| transform.compute_affine().inverse());
+            let to_model = origin * transform.compute_affine();
+
+            let wall_colors = wall_colors.cloned().unwrap_or_default();
+            colors.extend(
+                wall_colors
+                    .colors
+                    .vertex_colors(wall.len())
+                    .into_iter()
+                    .map(|color| color * wall_colors.intensity),
+            );
+
+            lines.extend(
+                wall.into_iter()
+                    .map(|(a, b)| (to_model.transform_point3(a), to_model.transform_point3(b))),
+            );
+        }
+
+        if lines.is_empty() {
+            warn!("Not saving model {name:?}: none of the selected entities are walls");
+            continue;
+        }
+
+        let model = Model::from_lines(&lines, ColorSpec::from_vertex_colors(&colors));
+        let path = format!("assets/models/{name}.{}", format.extension());
+        let contents = format.write(&model);
+
+        IoTaskPool::get()
+            .spawn(async move {
+                File::create(&path)
+                    .and_then(|mut file| file.write_all(contents.as_bytes()))
+                    .expect("Error while writing model to file");
+            })
+            .detach();
+    }
+}
+
+/// Whether `name` can go between `assets/models/` and an extension without ending up in another
+/// folder
+fn is_file_stem(name: &str) -> bool {
+    !name.is_empty()
+        && !name.contains(['/', '\\', ':'])
+        && Path::new(name).file_name() == Some(OsStr::new(name))
+}
+
+pub fn spawn_current_map(
+    mut events: EventReader<AssetEvent<Map>>,
+    current_map: Option<Res<CurrentMap>>,
+    maps: Res<Assets<Map>>,
+    settings: Res<StreamingSettings>,
+    spawned: Query<Entity, Or<(With<WallMesh>, With<MapPlacement>, With<MapSpawned>)>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut spawned_map: Local<Option<HandleId>>,
+    mut commands: Commands,
+) {
+    let Some(current_map) = current_map else {
         return;
+    };
+
+    // Opening another map that is already loaded doesn't send an event. The resource itself
+    // changes too often to go by, since its load state is kept up to date.
+    let mut changed = *spawned_map != Some(current_map.handle.id());
+    for event in events.iter() {
+        if let AssetEvent::Created { handle } | AssetEvent::Modified { handle } = event {
+            changed |= *handle == current_map.handle;
+        }
     }
 
-    let mut map_meshes = vec![];
+    if !changed {
+        return;
+    }
+
+    let Some(map) = maps.get(&current_map.handle) else {
+        return;
+    };
+
+    // The file on disk is authoritative, so this also replaces walls that were solidified in
+    // the editor; they are part of the map once it has been saved.
+    for entity in spawned.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+
+    spawn_map(map, settings.chunk_size, &mut meshes, &mut commands);
+    *spawned_map = Some(current_map.handle.id());
+}
 
-    for (transform, material, mesh) in walls.iter() {
-        let material = materials.get(material).unwrap();
-        let mesh = meshes.get(mesh).unwrap();
+/// Where the task writing a generated map leaves how it went
+type WriteResult = Arc<Mutex<Option<std::io::Result<()>>>>;
 
-        let base_color = mesh
-            .attribute(Mesh::ATTRIBUTE_COLOR)
-            .and_then(|attr| match attr {
-                VertexAttributeValues::Float32x4(values) => values.first(),
-                _ => None,
+pub fn generate_map(
+    mut events: EventReader<GenerateMap>,
+    generator: Res<MapGenerator>,
+    asset_server: Res<AssetServer>,
+    mut written: Local<Option<WriteResult>>,
+    mut commands: Commands,
+) {
+    if events.iter().next().is_some() {
+        let bytes = generator.generate().to_bytes(MapEncoding::Ron);
+        let result = WriteResult::default();
+
+        let task_result = result.clone();
+        IoTaskPool::get()
+            .spawn(async move {
+                let written = std::fs::write("assets/maps/generated.map.ron", bytes);
+                *task_result.lock().unwrap() = Some(written);
             })
-            .map(|v| Vec4::from(*v))
-            .unwrap_or(Vec4::from(Color::WHITE));
+            .detach();
 
-        if let Some(attr) = mesh.attribute(Mesh::ATTRIBUTE_POSITION) {
-            let lines = attr
-                .as_float3()
-                .unwrap()
-                .iter()
-                .tuples()
-                .map(|(a, b)| (Vec3::from(*a), Vec3::from(*b)))
-                .collect();
+        *written = Some(result);
+    }
 
-            let color = Color::from(base_color * Vec4::from(material.color.as_rgba_f32()));
+    // The map is only opened once it has been written, or the old one would be loaded
+    let Some(result) = written
+        .as_ref()
+        .and_then(|result| result.lock().unwrap().take())
+    else {
+        return;
+    };
+    *written = None;
 
-            map_meshes.push(MapMesh {
-                transform: *transform,
-                lines: LineList { lines, color },
-            });
-        }
+    if let Err(err) = result {
+        error!("Could not write the generated map: {err}");
+        return;
     }
 
-    let scene = Map { map_meshes };
-
-    IoTaskPool::get()
-        .spawn(async move {
-            // Write the scene RON data to file
-            File::create("assets/maps/world.map.ron")
-                .and_then(|mut file| {
-                    file.write(
-                        ron::ser::to_string_pretty(&scene, ron::ser::PrettyConfig::default())
-                            .unwrap()
-                            .as_bytes(),
-                    )
-                })
-                .expect("Error while writing scene to file");
-        })
-        .detach();
+    // If it was open already, the file watcher reloads it instead
+    commands.insert_resource(CurrentMap::load("maps/generated.map.ron".to_owned(), &asset_server));
 }
 
-pub fn load_scene<P: AsRef<Path>>(
-    path: P,
-    mut commands: Commands,
-    mut meshes: ResMut<Assets<Mesh>>,
+pub fn report_map_load_state(
+    current_map: Option<ResMut<CurrentMap>>,
+    asset_server: Res<AssetServer>,
 ) {
-    let file = File::open(path).unwrap();
-    let scene: Map = ron::de::from_reader(file).unwrap();
-
-    for map_mesh in scene.map_meshes {
-        let mut collider_vertices = vec![];
-        let mut collider_indices = vec![];
-
-        for (from, to) in &map_mesh.lines.lines {
-            if from.z == 0.0 && to.z == 0.0 {
-                let idx = collider_vertices.len() as u32;
-
-                collider_vertices.extend_from_slice(&[
-                    *from - Vec3::Z,
-                    *from + Vec3::Z,
-                    *to - Vec3::Z,
-                    *to + Vec3::Z,
-                ]);
-
-                collider_indices.push([idx, idx + 2, idx + 1]);
-                collider_indices.push([idx + 1, idx + 2, idx + 3]);
+    let Some(mut current_map) = current_map else {
+        return;
+    };
+
+    let load_state = asset_server.get_load_state(&current_map.handle);
+    if load_state == current_map.load_state {
+        return;
+    }
+
+    if load_state == LoadState::Failed {
+        let path = asset_server.get_handle_path(&current_map.handle);
+        error!(
+            "Failed to load map {:?}, keeping the previous version",
+            path.as_ref().map(|path| path.path())
+        );
+    }
+
+    current_map.load_state = load_state;
+}
+
+/// Spawns the map's walls and placements. Walls start out hidden and without collisions, until
+/// [`stream_chunks`] finds them close enough.
+pub fn spawn_map(map: &Map, chunk_size: f32, meshes: &mut Assets<Mesh>, commands: &mut Commands) {
+    let walls: Vec<_> = map
+        .map_meshes
+        .iter()
+        .map(|map_mesh| {
+            let colors = WallColors {
+                colors: map_mesh.colors.clone(),
+                intensity: map_mesh.intensity,
+            };
+
+            let mut wall = WallBundle::new(
+                map_mesh.lines.clone(),
+                colors,
+                map_mesh.surface.clone(),
+                map_mesh.transform,
+                meshes,
+            );
+            wall.mesh.visibility = Visibility::Hidden;
+
+            let bounds = wall_bounds(&map_mesh.lines, &map_mesh.transform);
+            let mut entity = commands.spawn((wall, ColliderDisabled));
+            if let Some(source) = &map_mesh.source {
+                entity.insert(source.clone());
             }
+
+            (entity.id(), bounds)
+        })
+        .collect();
+
+    commands.insert_resource(MapChunks::new(walls, chunk_size));
+
+    for placement in &map.placements {
+        spawn_placement(placement, commands);
+    }
+}
+
+fn spawn_placement(placement: &Placement, commands: &mut Commands) {
+    let name = Name::new(format!("{:?}", placement.kind));
+    let transform = TransformBundle::from_transform(placement.transform);
+
+    let mut entity = commands.spawn((
+        name.clone(),
+        transform,
+        VisibilityBundle::default(),
+        MapPlacement(placement.clone()),
+    ));
+
+    match placement.kind {
+        PlacementKind::PlayerStart => {
+            entity.insert(PlayerStart);
+        }
+        PlacementKind::Marker(ref name) => {
+            entity.insert(MapMarker { name: name.clone() });
+        }
+        // Enemies and pickups go away when they are killed or collected, so they get entities of
+        // their own and the placement stays around to be saved
+        PlacementKind::EnemySpawn(ref kind) => {
+            commands.spawn((
+                name,
+                transform,
+                VisibilityBundle::default(),
+                MapSpawned,
+                EnemySpawnToken {
+                    archetype: kind.archetype().to_owned(),
+                    route: placement.text("route").map(str::to_owned),
+                },
+            ));
         }
+        PlacementKind::Pickup(kind) => {
+            commands.spawn((name, transform, VisibilityBundle::default(), MapSpawned, Pickup {
+                kind,
+                amount: placement.number("amount").unwrap_or(1.),
+            }));
+        }
+    }
+}
 
-        let mesh = meshes.add(Mesh::from(map_mesh.lines));
-
-        commands.spawn((
-            MaterialMeshBundle::<StandardMaterial> {
-                mesh,
-                transform: map_mesh.transform,
-                ..Default::default()
-            },
-            Collider::trimesh(collider_vertices, collider_indices),
-            WallMesh,
-        ));
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::SystemState;
+
+    use super::*;
+    use crate::pickup::PickupKind;
+
+    #[test]
+    fn saves_collected_pickups() {
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, AssetPlugin::default()))
+            .add_asset::<Mesh>();
+
+        let placement = Placement {
+            transform: Transform::from_xyz(2., 3., 0.),
+            kind: PlacementKind::Pickup(PickupKind::Health),
+            properties: default(),
+        };
+        let map = Map::new(vec![], vec![placement]);
+
+        let mut state = SystemState::<(Commands, ResMut<Assets<Mesh>>)>::new(&mut app.world);
+        let (mut commands, mut meshes) = state.get_mut(&mut app.world);
+        spawn_map(&map, 32., &mut meshes, &mut commands);
+        state.apply(&mut app.world);
+
+        // Collecting the pickup despawns it
+        let pickup = app
+            .world
+            .query_filtered::<Entity, With<Pickup>>()
+            .single(&app.world);
+        app.world.despawn(pickup);
+
+        let mut contents = SystemState::<MapContents>::new(&mut app.world);
+        let saved = contents.get(&app.world).to_map();
+
+        let [placement] = &saved.placements[..] else {
+            panic!("expected one placement, got {}", saved.placements.len());
+        };
+        assert!(matches!(placement.kind, PlacementKind::Pickup(PickupKind::Health)));
+        assert_eq!(placement.transform.translation, Vec3::new(2., 3., 0.));
     }
 }
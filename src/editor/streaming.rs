@@ -0,0 +1,145 @@
+use bevy::{prelude::*, utils::HashMap};
+use bevy_rapier3d::prelude::ColliderDisabled;
+
+use super::mesh::WallMesh;
+use crate::player::PlayerShip;
+
+/// How the map's walls are grouped into chunks, and how close the player or a camera has to be for
+/// them to be drawn and collided with
+#[derive(Resource, Reflect)]
+#[reflect(Resource)]
+pub struct StreamingSettings {
+    /// Width of the square chunks walls are grouped into. Only affects maps loaded after a change.
+    pub chunk_size: f32,
+    /// Chunks closer than this are shown. Cameras reach further by their height above the play
+    /// plane, so zooming out shows more of the map.
+    pub load_distance: f32,
+    /// Shown chunks are hidden again once they are further than this. It should be a bit more
+    /// than `load_distance`, so chunks right at the edge don't keep flickering.
+    pub unload_distance: f32,
+}
+
+impl Default for StreamingSettings {
+    fn default() -> Self {
+        Self {
+            chunk_size: 32.,
+            load_distance: 64.,
+            unload_distance: 80.,
+        }
+    }
+}
+
+/// Walls whose bounds have their middle in the same square
+struct Chunk {
+    walls: Vec<Entity>,
+    /// Where the walls are on the play plane, all together
+    bounds: Rect,
+    shown: bool,
+}
+
+impl Chunk {
+    fn distance(&self, point: Vec2) -> f32 {
+        point
+            .clamp(self.bounds.min, self.bounds.max)
+            .distance(point)
+    }
+}
+
+/// The walls of the current map, grouped into chunks that are shown and hidden by
+/// [`stream_chunks`]. Hidden walls get [`ColliderDisabled`], so the physics engine leaves them out
+/// too. Walls made in the editor aren't part of this, and are always shown.
+#[derive(Resource, Default)]
+pub struct MapChunks {
+    chunks: Vec<Chunk>,
+}
+
+impl MapChunks {
+    /// Takes each wall with its bounds on the play plane. Walls stay in one piece, in the chunk
+    /// their middle is in.
+    pub fn new(walls: impl IntoIterator<Item = (Entity, Rect)>, chunk_size: f32) -> Self {
+        let mut chunks: HashMap<IVec2, Chunk> = HashMap::new();
+
+        for (wall, bounds) in walls {
+            let key = (bounds.center() / chunk_size).floor().as_ivec2();
+            let chunk = chunks
+                .entry(key)
+                .or_insert(Chunk { walls: vec![], bounds, shown: false });
+
+            chunk.walls.push(wall);
+            chunk.bounds = chunk.bounds.union(bounds);
+        }
+
+        Self {
+            chunks: chunks.into_values().collect(),
+        }
+    }
+}
+
+/// Where lines end up on the play plane once `transform` has been applied
+pub fn wall_bounds(lines: &[(Vec3, Vec3)], transform: &Transform) -> Rect {
+    let matrix = transform.compute_affine();
+    let mut points = lines
+        .iter()
+        .flat_map(|(a, b)| [*a, *b])
+        .map(|point| matrix.transform_point3(point).truncate());
+
+    let first = points.next().unwrap_or_default();
+    points.fold(Rect::from_corners(first, first), |bounds, point| bounds.union_point(point))
+}
+
+pub fn stream_chunks(
+    settings: Res<StreamingSettings>,
+    chunks: Option<ResMut<MapChunks>>,
+    viewers: Query<(&GlobalTransform, Option<&Camera>), Or<(With<PlayerShip>, With<Camera>)>>,
+    mut walls: Query<&mut Visibility, With<WallMesh>>,
+    mut commands: Commands,
+) {
+    let Some(mut chunks) = chunks else {
+        return;
+    };
+
+    let viewers: Vec<_> = viewers
+        .iter()
+        .map(|(transform, camera)| {
+            let translation = transform.translation();
+            let reach = camera.map_or(0., |_| translation.z.abs());
+            (translation.truncate(), reach)
+        })
+        .collect();
+
+    for chunk in chunks.chunks.iter_mut() {
+        let distance = viewers
+            .iter()
+            .map(|(position, reach)| chunk.distance(*position) - reach)
+            .fold(f32::INFINITY, f32::min);
+
+        let shown = match chunk.shown {
+            false => distance <= settings.load_distance,
+            true => distance <= settings.unload_distance,
+        };
+
+        if shown == chunk.shown {
+            continue;
+        }
+
+        // Walls deleted in the editor are gone for good
+        chunk.walls.retain(|wall| walls.contains(*wall));
+
+        for wall in &chunk.walls {
+            let Ok(mut visibility) = walls.get_mut(*wall) else {
+                continue;
+            };
+
+            let mut wall = commands.entity(*wall);
+            if shown {
+                *visibility = Visibility::Inherited;
+                wall.remove::<ColliderDisabled>();
+            } else {
+                *visibility = Visibility::Hidden;
+                wall.insert(ColliderDisabled);
+            }
+        }
+
+        chunk.shown = shown;
+    }
+}
@@ -22,10 +22,16 @@ use egui_gizmo::{GizmoMode, GizmoVisuals};
 use heck::ToTitleCase;
 
 use super::{
+    export::{ConvertMap, ExportModel, ExportSvg},
+    import::ImportFile,
     mesh::{DeleteConnectedLines, ExplodeMesh, MeshLine, Solidify},
-    scene::SaveScene,
+    scene::{GenerateMap, ModelFormat, SaveModel, SaveScene},
     EditorCamera, EditorWindow,
 };
+use crate::assets::{
+    map::{generator::MapGenerator, MapEncoding},
+    model::Model,
+};
 
 pub struct EditorUiPlugin;
 
@@ -75,7 +81,17 @@ fn show_ui_system(world: &mut World) {
             ui.horizontal(|ui| {
                 ui.menu_button("Map", |ui| {
                     if ui.button("Save").clicked() {
-                        world.send_event(SaveScene);
+                        world.send_event(SaveScene { encoding: MapEncoding::Ron });
+                        ui.close_menu();
+                    }
+
+                    if ui.button("Save binary").clicked() {
+                        world.send_event(SaveScene { encoding: MapEncoding::Binary });
+                        ui.close_menu();
+                    }
+
+                    if ui.button("Export SVG").clicked() {
+                        world.send_event(ExportSvg);
                         ui.close_menu();
                     }
                 });
@@ -125,6 +141,8 @@ pub struct UiState {
     pub gizmo_mode: GizmoMode,
     pub hovering_camera: bool,
     pub new_mesh_props: NewMeshProperties,
+    pub model_name: String,
+    pub import_path: String,
 }
 
 impl Default for UiState {
@@ -139,6 +157,8 @@ impl Default for UiState {
                 color: Color::rgb(0., 0.5, 1.),
                 intensity: 2.0,
             },
+            model_name: "untitled".to_owned(),
+            import_path: String::new(),
         }
     }
 }
@@ -276,6 +296,79 @@ impl egui_dock::TabViewer for TabViewer<'_> {
                         ui.checkbox(&mut debug_render.enabled, "Show hitboxes");
                     });
 
+                ui.separator();
+
+                egui::CollapsingHeader::new("Import")
+                    .default_open(false)
+                    .show(ui, |ui| {
+                        ui.text_edit_singleline(&mut self.state.import_path);
+
+                        ui.horizontal(|ui| {
+                            if ui.button("Import file").clicked() {
+                                self.world.send_event(ImportFile {
+                                    path: self.state.import_path.clone().into(),
+                                });
+                            }
+
+                            if ui.button("Convert map").clicked() {
+                                self.world.send_event(ConvertMap {
+                                    path: self.state.import_path.clone().into(),
+                                });
+                            }
+                        });
+                    });
+
+                egui::CollapsingHeader::new("Generate")
+                    .default_open(false)
+                    .show(ui, |ui| {
+                        let mut generator = self.world.resource_mut::<MapGenerator>();
+
+                        ui.horizontal(|ui| {
+                            ui.label("Seed");
+                            ui.add(egui::DragValue::new(&mut generator.seed));
+
+                            if ui.button("Random").clicked() {
+                                generator.seed = rand::random();
+                            }
+                        });
+
+                        ui.label("The other settings are under Resources");
+
+                        if ui.button("Generate map").clicked() {
+                            self.world.send_event(GenerateMap);
+                        }
+                    });
+
+                egui::CollapsingHeader::new("Export models")
+                    .default_open(false)
+                    .show(ui, |ui| {
+                        let asset_server = self.world.resource::<AssetServer>();
+                        let mut models: Vec<_> = self
+                            .world
+                            .resource::<Assets<Model>>()
+                            .ids()
+                            .filter_map(|id| {
+                                let path = asset_server.get_handle_path(id)?;
+                                Some((path.path().display().to_string(), id))
+                            })
+                            .collect();
+                        models.sort();
+
+                        for (path, model) in models {
+                            ui.horizontal(|ui| {
+                                ui.label(path);
+
+                                for (label, format) in
+                                    [("OBJ", ModelFormat::Obj), ("SVG", ModelFormat::Svg)]
+                                {
+                                    if ui.button(label).clicked() {
+                                        self.world.send_event(ExportModel { model, format });
+                                    }
+                                }
+                            });
+                        }
+                    });
+
                 // }
                 // EguiWindow::Options => {
                 ui.separator();
@@ -298,6 +391,24 @@ impl egui_dock::TabViewer for TabViewer<'_> {
                         self.world.send_event(DeleteConnectedLines);
                     }
 
+                    ui.horizontal(|ui| {
+                        ui.text_edit_singleline(&mut self.state.model_name);
+
+                        for (label, format) in [
+                            ("Save as model", ModelFormat::Ron),
+                            ("SVG", ModelFormat::Svg),
+                            ("OBJ", ModelFormat::Obj),
+                        ] {
+                            if ui.button(label).clicked() {
+                                self.world.send_event(SaveModel {
+                                    name: self.state.model_name.clone(),
+                                    walls: self.state.selected_entities.as_slice().to_vec(),
+                                    format,
+                                });
+                            }
+                        }
+                    });
+
                     ui.separator();
                 }
 
@@ -0,0 +1,309 @@
+//! Enemies described by `.enemy.ron` files in `assets/enemies`, and spawned from
+//! [`EnemySpawnToken`]s that name them
+
+use bevy::{
+    asset::{AssetLoader, LoadState, LoadedAsset},
+    prelude::*,
+    reflect::{TypePath, TypeUuid},
+};
+use bevy_rapier3d::prelude::*;
+use big_brain::{prelude::Highest, thinker::Thinker};
+use serde::Deserialize;
+
+use super::{
+    behaviour::{
+        chase::{Chase, ChaseMemory, Chasing, PlayerChaser},
+        patrol::{Patrol, Patroller, Patrolling},
+        wander::{Wander, Wanderer, Wandering},
+    },
+    navigation::NavAgent,
+    ContactDamage, Enemy,
+};
+use crate::{
+    collision_groups,
+    damageable::Damageable,
+    editor::scene::MapSpawned,
+    player::PlayerShip,
+    team::Team,
+    utils::zlock::ZLocked,
+    weapon::{Weapon, WeaponTrigger},
+};
+
+/// A kind of enemy, as written in a `.enemy.ron` file
+#[derive(Deserialize, TypeUuid, TypePath, Debug)]
+#[uuid = "0c1a4b8e-5f63-4d2a-9b7e-3e8d6f41a2c5"]
+pub struct EnemyArchetype {
+    /// Shown in the editor's entity list
+    pub name: String,
+    /// Path of the `.mdl.ron` model drawn for the enemy
+    pub model: String,
+    pub collider: EnemyCollider,
+    pub health: f32,
+    /// Damage done to the player on every frame they touch. 0 doesn't add [`ContactDamage`].
+    #[serde(default)]
+    pub contact_damage: f32,
+    #[serde(default = "enemy_team")]
+    pub team: Team,
+    /// Whether the enemy keeps its rotation, rather than rolling as it is pushed around
+    #[serde(default)]
+    pub lock_rotation: bool,
+    #[serde(default)]
+    pub weapons: Vec<EnemyWeapon>,
+    /// What the enemy's [`Thinker`] picks between, the highest scoring first
+    #[serde(default)]
+    pub behaviours: Vec<Behaviour>,
+}
+
+fn enemy_team() -> Team {
+    Team::Enemy
+}
+
+#[derive(Deserialize, Clone, Copy, Debug)]
+pub enum EnemyCollider {
+    Ball(f32),
+    /// Half of the box's width, height and depth
+    Cuboid(Vec3),
+}
+
+impl EnemyCollider {
+    fn collider(self) -> Collider {
+        match self {
+            EnemyCollider::Ball(radius) => Collider::ball(radius),
+            EnemyCollider::Cuboid(half_size) => {
+                Collider::cuboid(half_size.x, half_size.y, half_size.z)
+            }
+        }
+    }
+}
+
+/// A weapon mounted on an enemy, which fires at the player while they are in range
+#[derive(Deserialize, Clone, Debug)]
+pub struct EnemyWeapon {
+    /// Where the weapon is, relative to the enemy
+    #[serde(default)]
+    pub offset: Vec3,
+    pub range: f32,
+    pub cooldown: f64,
+    pub damage: f32,
+    pub velocity: f32,
+    #[serde(default)]
+    pub spread: f32,
+    pub color: Color,
+}
+
+/// Behaviours an archetype can give its enemies, each adding a scorer and an action to its
+/// [`Thinker`] along with the components they need
+#[derive(Deserialize, Clone, Debug)]
+pub enum Behaviour {
+    /// Pushes towards the player when they are closer than `max_proximity`. With `los`, walls
+    /// hide the player, and the enemy goes to where it last saw them until `give_up_after`
+    /// seconds have passed.
+    Chase {
+        max_proximity: f32,
+        #[serde(default)]
+        los: bool,
+        #[serde(default = "default_give_up_after")]
+        give_up_after: f32,
+    },
+    /// Drifts between random points up to `leash` away from where the enemy was spawned
+    Wander { leash: f32, speed: f32 },
+    /// Goes around the waypoints of `route`, which are markers in the map. A `route` property on
+    /// the enemy's placement picks a different one.
+    Patrol { route: String, speed: f32 },
+}
+
+fn default_give_up_after() -> f32 {
+    5.
+}
+
+/// Aims a weapon on an enemy at the player, and fires it while they are within `range`
+#[derive(Component, Reflect, Default)]
+#[reflect(Component)]
+pub struct WeaponRange {
+    pub range: f32,
+}
+
+/// Marks where an enemy will be spawned from the archetype in
+/// `assets/enemies/{archetype}.enemy.ron`. The token is despawned once the enemy is.
+#[derive(Component)]
+pub struct EnemySpawnToken {
+    pub archetype: String,
+    /// Patrols this route instead of the archetype's, from the placement's `route` property
+    pub route: Option<String>,
+}
+
+pub struct EnemyArchetypeLoader;
+impl AssetLoader for EnemyArchetypeLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut bevy::asset::LoadContext,
+    ) -> bevy::utils::BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+        Box::pin(async move {
+            let archetype: EnemyArchetype = ron::de::from_bytes(bytes)?;
+            load_context.set_default_asset(LoadedAsset::new(archetype));
+
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["enemy.ron"]
+    }
+}
+
+pub fn load_archetypes(
+    tokens: Query<(Entity, &EnemySpawnToken), Added<EnemySpawnToken>>,
+    asset_server: Res<AssetServer>,
+    mut commands: Commands,
+) {
+    for (entity, token) in tokens.iter() {
+        let archetype: Handle<EnemyArchetype> =
+            asset_server.load(format!("enemies/{}.enemy.ron", token.archetype));
+
+        commands.entity(entity).insert(archetype);
+    }
+}
+
+/// Turns tokens into enemies once their archetypes have loaded
+pub fn spawn_enemies(
+    tokens: Query<(
+        Entity,
+        &Transform,
+        &EnemySpawnToken,
+        &Handle<EnemyArchetype>,
+        Option<&MapSpawned>,
+    )>,
+    archetypes: Res<Assets<EnemyArchetype>>,
+    asset_server: Res<AssetServer>,
+    mut commands: Commands,
+) {
+    for (entity, transform, token, handle, map_spawned) in tokens.iter() {
+        let Some(archetype) = archetypes.get(handle) else {
+            if asset_server.get_load_state(handle) == LoadState::Failed {
+                warn!("Failed to load enemy archetype {:?}", token.archetype);
+                commands.entity(entity).despawn();
+            }
+
+            continue;
+        };
+
+        commands.entity(entity).despawn();
+
+        let mut enemy = commands.spawn((
+            Name::new(archetype.name.clone()),
+            Enemy,
+            MaterialMeshBundle::<StandardMaterial> {
+                mesh: asset_server.load(format!("{}#mesh", archetype.model)),
+                transform: *transform,
+                ..default()
+            },
+            CollisionGroups::new(collision_groups::ENEMY, collision_groups::ALL),
+            ExternalImpulse::default(),
+            RigidBody::Dynamic,
+            archetype.collider.collider(),
+            ZLocked { angular: archetype.lock_rotation },
+            Damageable {
+                health: archetype.health,
+                max_health: archetype.health,
+            },
+            archetype.team,
+        ));
+
+        // Enemies of the map go away with it when it is spawned again
+        if map_spawned.is_some() {
+            enemy.insert(MapSpawned);
+        }
+
+        if archetype.contact_damage > 0. {
+            enemy.insert(ContactDamage { damage: archetype.contact_damage });
+        }
+
+        if !archetype.behaviours.is_empty() {
+            // Whatever moves the enemy steers around walls
+            enemy.insert(NavAgent::default());
+
+            let mut thinker = Thinker::build()
+                .label(archetype.name.clone())
+                .picker(Highest);
+
+            for behaviour in &archetype.behaviours {
+                thinker = match *behaviour {
+                    Behaviour::Chase { max_proximity, los, give_up_after } => {
+                        enemy.insert((
+                            PlayerChaser { max_proximity, los, give_up_after },
+                            ChaseMemory::default(),
+                        ));
+                        thinker.when(Chase, Chasing)
+                    }
+                    Behaviour::Wander { leash, speed } => {
+                        enemy.insert(Wanderer::new(leash, speed, transform.translation));
+                        thinker.when(Wander, Wandering)
+                    }
+                    Behaviour::Patrol { ref route, speed } => {
+                        let route = token.route.as_deref().unwrap_or(route);
+
+                        enemy.insert(Patroller::new(route.to_owned(), speed));
+                        thinker.when(Patrol, Patrolling)
+                    }
+                };
+            }
+
+            enemy.insert(thinker);
+        }
+
+        enemy.with_children(|children| {
+            for weapon in &archetype.weapons {
+                children.spawn((
+                    Name::new("Enemy weapon"),
+                    TransformBundle::from_transform(Transform::from_translation(weapon.offset)),
+                    Weapon {
+                        cooldown: weapon.cooldown,
+                        next_shot: 0.,
+                        damage: weapon.damage,
+                        velocity: weapon.velocity,
+                        spread: weapon.spread,
+                        color: weapon.color,
+                    },
+                    WeaponTrigger::default(),
+                    WeaponRange { range: weapon.range },
+                    archetype.team,
+                ));
+            }
+        });
+    }
+}
+
+pub fn aim_weapons(
+    players: Query<&GlobalTransform, With<PlayerShip>>,
+    mut weapons: Query<(
+        &mut Transform,
+        &GlobalTransform,
+        &Parent,
+        &WeaponRange,
+        &mut WeaponTrigger,
+    )>,
+    parents: Query<&GlobalTransform>,
+) {
+    let Some(player) = players.iter().next() else {
+        return;
+    };
+    let target = player.translation();
+
+    for (mut transform, global_transform, parent, weapon, mut trigger) in weapons.iter_mut() {
+        let offset = (target - global_transform.translation()).truncate();
+        if offset.length() > weapon.range {
+            continue;
+        }
+
+        // The weapon points along its X axis, but it turns along with the enemy it is on
+        let aim = Quat::from_rotation_z(offset.y.atan2(offset.x));
+        let parent_rotation = parents
+            .get(parent.get())
+            .map(|parent| parent.compute_transform().rotation)
+            .unwrap_or_default();
+
+        transform.rotation = parent_rotation.inverse() * aim;
+        trigger.0 = true;
+    }
+}
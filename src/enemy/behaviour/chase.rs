@@ -2,15 +2,16 @@ use bevy::prelude::*;
 use bevy_rapier3d::prelude::*;
 use big_brain::prelude::*;
 
-use crate::player::PlayerShip;
+use crate::{collision_groups, enemy::navigation::NavAgent, player::PlayerShip};
 
 pub struct PlayerChaserPlugin;
 
 impl Plugin for PlayerChaserPlugin {
     fn build(&self, app: &mut App) {
-        app.add_system(chase_scorer_system)
-            .add_system(chase_action_system)
-            .register_type::<PlayerChaser>();
+        app.add_systems(Update, (look_for_player, chase_scorer_system).chain())
+            .add_systems(Update, chase_action_system)
+            .register_type::<PlayerChaser>()
+            .register_type::<ChaseMemory>();
     }
 }
 
@@ -18,7 +19,20 @@ impl Plugin for PlayerChaserPlugin {
 #[reflect(Component)]
 pub struct PlayerChaser {
     pub max_proximity: f32,
+    /// Whether walls hide the player. Without it, the player is seen whenever they are close.
     pub los: bool,
+    /// How many seconds the chaser keeps going after losing sight of the player
+    pub give_up_after: f32,
+}
+
+/// Where a [`PlayerChaser`] last saw the player
+#[derive(Component, Reflect, Default)]
+#[reflect(Component)]
+pub struct ChaseMemory {
+    pub last_seen: Option<Vec3>,
+    pub seeing: bool,
+    /// Seconds since the player was last seen
+    pub lost_for: f32,
 }
 
 #[derive(Component, ScorerBuilder, Debug, Clone)]
@@ -27,48 +41,115 @@ pub struct Chase;
 #[derive(Component, ActionBuilder, Debug, Clone)]
 pub struct Chasing;
 
-pub fn chase_scorer_system(
+/// How much a chaser wants to go to where it last saw the player, compared to chasing them while
+/// it can see them
+const SEARCH_SCORE: f32 = 0.5;
+
+/// How close a chaser has to get to where it last saw the player to stop there
+const SEARCH_ARRIVAL_DISTANCE: f32 = 1.;
+
+pub fn look_for_player(
+    time: Res<Time>,
+    context: Res<RapierContext>,
     players: Query<&Transform, With<PlayerShip>>,
-    enemies: Query<(&Transform, &PlayerChaser)>,
-    mut query: Query<(&Actor, &mut Score, &ScorerSpan), With<Chase>>,
+    mut chasers: Query<(&Transform, &PlayerChaser, &mut ChaseMemory)>,
 ) {
-    for (Actor(actor), mut score, _span) in &mut query {
-        if let Ok((transform, chaser)) = enemies.get(*actor) {
-            for player in &mut players.iter() {
-                let player_distance = player.translation.distance_squared(transform.translation);
+    for (transform, chaser, mut memory) in &mut chasers {
+        let seen = players.iter().find(|player| {
+            let offset = player.translation - transform.translation;
 
-                if player_distance < chaser.max_proximity.powi(2) {
-                    score.set(1.);
-                }
+            if offset.length_squared() > chaser.max_proximity.powi(2) {
+                return false;
+            }
+
+            // Only walls block the view, not other enemies or the player's bullets
+            !chaser.los
+                || context
+                    .cast_ray(
+                        transform.translation,
+                        offset,
+                        1.,
+                        true,
+                        QueryFilter::default().groups(CollisionGroups::new(
+                            collision_groups::ENEMY,
+                            collision_groups::WALL,
+                        )),
+                    )
+                    .is_none()
+        });
+
+        memory.seeing = seen.is_some();
+
+        if let Some(player) = seen {
+            memory.last_seen = Some(player.translation);
+            memory.lost_for = 0.;
+        } else if memory.last_seen.is_some() {
+            memory.lost_for += time.delta_seconds();
+
+            if memory.lost_for > chaser.give_up_after {
+                memory.last_seen = None;
             }
         }
     }
 }
 
+pub fn chase_scorer_system(
+    memories: Query<&ChaseMemory>,
+    mut query: Query<(&Actor, &mut Score, &ScorerSpan), With<Chase>>,
+) {
+    for (Actor(actor), mut score, _span) in &mut query {
+        let Ok(memory) = memories.get(*actor) else {
+            continue;
+        };
+
+        score.set(match memory {
+            ChaseMemory { seeing: true, .. } => 1.,
+            ChaseMemory { last_seen: Some(_), .. } => SEARCH_SCORE,
+            _ => 0.,
+        });
+    }
+}
+
 pub fn chase_action_system(
     time: Res<Time>,
-    mut enemies: Query<(&Transform, &mut ExternalImpulse), Without<PlayerShip>>,
-    players: Query<(&Transform, &PlayerShip)>,
+    mut enemies: Query<
+        (&Transform, &ChaseMemory, &mut NavAgent, &mut ExternalImpulse),
+        Without<PlayerShip>,
+    >,
     mut query: Query<(&Actor, &mut ActionState, &ActionSpan, &Chasing)>,
 ) {
     for (Actor(actor), mut state, span, _) in &mut query {
         let _guard = span.span().enter();
 
-        if let Ok((transform, mut impulse)) = enemies.get_mut(*actor) {
+        if let Ok((transform, memory, mut agent, mut impulse)) = enemies.get_mut(*actor) {
             match *state {
                 ActionState::Requested => {
                     debug!("start chasing!");
                     *state = ActionState::Executing;
                 }
                 ActionState::Executing => {
-                    let player = players.iter().next().unwrap();
+                    let Some(target) = memory.last_seen else {
+                        debug!("lost the player");
+                        agent.destination = None;
+                        *state = ActionState::Failure;
+                        continue;
+                    };
+
+                    // Wait where the player was last seen, in case they come back
+                    let distance = target.distance(transform.translation);
+                    if !memory.seeing && distance < SEARCH_ARRIVAL_DISTANCE {
+                        agent.destination = None;
+                        continue;
+                    }
 
-                    let direction = (player.0.translation - transform.translation).normalize();
+                    agent.destination = Some(target);
+                    let direction = agent.steer(transform.translation);
 
                     impulse.impulse += direction * 10. * time.delta_seconds();
                 }
                 ActionState::Cancelled => {
                     debug!("chase cancelled");
+                    agent.destination = None;
                     *state = ActionState::Failure;
                 }
                 _ => {}
@@ -0,0 +1,168 @@
+use bevy::{prelude::*, utils::HashMap};
+use bevy_rapier3d::prelude::*;
+use big_brain::prelude::*;
+
+use crate::{
+    editor::scene::{MapMarker, MapPlacement},
+    enemy::navigation::NavAgent,
+    player::PlayerShip,
+};
+
+pub struct PatrollerPlugin;
+
+impl Plugin for PatrollerPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<PatrolRoutes>()
+            .add_systems(Update, find_patrol_routes)
+            .add_systems(
+                Update,
+                (patrol_scorer_system, patrol_action_system).after(find_patrol_routes),
+            )
+            .register_type::<Patroller>();
+    }
+}
+
+/// Goes around the waypoints of a route, in order
+#[derive(Component, Reflect, Default)]
+#[reflect(Component)]
+pub struct Patroller {
+    pub route: String,
+    pub speed: f32,
+    /// The waypoint being headed for
+    next: usize,
+}
+
+impl Patroller {
+    pub fn new(route: String, speed: f32) -> Self {
+        Self { route, speed, next: 0 }
+    }
+}
+
+/// Waypoints placed in the map as markers with a `route` property naming their route, and a
+/// `waypoint` number for their place along it. Patrols loop back to the first waypoint after the
+/// last.
+#[derive(Resource, Default)]
+pub struct PatrolRoutes {
+    pub routes: HashMap<String, Vec<Vec3>>,
+}
+
+#[derive(Component, ScorerBuilder, Debug, Clone)]
+pub struct Patrol;
+
+#[derive(Component, ActionBuilder, Debug, Clone)]
+pub struct Patrolling;
+
+/// Patrolling beats wandering, but anything more urgent comes first
+const PATROL_SCORE: f32 = 0.2;
+
+const PATROL_ARRIVAL_DISTANCE: f32 = 1.;
+
+pub fn find_patrol_routes(
+    markers: Query<(&MapPlacement, &Transform), With<MapMarker>>,
+    changed: Query<(), (With<MapMarker>, Or<(Changed<MapPlacement>, Changed<Transform>)>)>,
+    mut removed: RemovedComponents<MapMarker>,
+    mut routes: ResMut<PatrolRoutes>,
+) {
+    let removed = removed.iter().count() > 0;
+    if changed.is_empty() && !removed {
+        return;
+    }
+
+    let mut waypoints: HashMap<String, Vec<(f32, Vec3)>> = HashMap::new();
+    for (MapPlacement(placement), transform) in markers.iter() {
+        let (Some(route), Some(index)) = (placement.text("route"), placement.number("waypoint"))
+        else {
+            continue;
+        };
+
+        waypoints
+            .entry(route.to_owned())
+            .or_default()
+            .push((index, transform.translation));
+    }
+
+    routes.routes = waypoints
+        .into_iter()
+        .map(|(route, mut points)| {
+            points.sort_by(|(a, _), (b, _)| a.total_cmp(b));
+            (route, points.into_iter().map(|(_, point)| point).collect())
+        })
+        .collect();
+}
+
+pub fn patrol_scorer_system(
+    routes: Res<PatrolRoutes>,
+    patrollers: Query<&Patroller>,
+    mut query: Query<(&Actor, &mut Score), With<Patrol>>,
+) {
+    for (Actor(actor), mut score) in &mut query {
+        let Ok(patroller) = patrollers.get(*actor) else {
+            continue;
+        };
+
+        let has_route = routes.routes.contains_key(&patroller.route);
+        score.set(if has_route { PATROL_SCORE } else { 0. });
+    }
+}
+
+pub fn patrol_action_system(
+    time: Res<Time>,
+    routes: Res<PatrolRoutes>,
+    mut enemies: Query<
+        (&Transform, &mut Patroller, &mut NavAgent, &mut ExternalImpulse),
+        Without<PlayerShip>,
+    >,
+    mut query: Query<(&Actor, &mut ActionState, &ActionSpan), With<Patrolling>>,
+) {
+    for (Actor(actor), mut state, span) in &mut query {
+        let _guard = span.span().enter();
+
+        let Ok((transform, mut patroller, mut agent, mut impulse)) = enemies.get_mut(*actor) else {
+            continue;
+        };
+
+        let Some(waypoints) = routes.routes.get(&patroller.route) else {
+            agent.destination = None;
+            *state = ActionState::Failure;
+            continue;
+        };
+
+        match *state {
+            ActionState::Requested => {
+                debug!("start patrolling {}", patroller.route);
+
+                // Pick the route up from wherever is closest, like after a chase
+                patroller.next = waypoints
+                    .iter()
+                    .enumerate()
+                    .min_by(|(_, a), (_, b)| {
+                        let a = a.distance_squared(transform.translation);
+                        let b = b.distance_squared(transform.translation);
+                        a.total_cmp(&b)
+                    })
+                    .map_or(0, |(index, _)| index);
+
+                *state = ActionState::Executing;
+            }
+            ActionState::Executing => {
+                let mut target = waypoints[patroller.next % waypoints.len()];
+
+                if target.distance(transform.translation) < PATROL_ARRIVAL_DISTANCE {
+                    patroller.next = (patroller.next + 1) % waypoints.len();
+                    target = waypoints[patroller.next];
+                }
+
+                agent.destination = Some(target);
+                let direction = agent.steer(transform.translation);
+
+                impulse.impulse += direction * patroller.speed * time.delta_seconds();
+            }
+            ActionState::Cancelled => {
+                debug!("patrol cancelled");
+                agent.destination = None;
+                *state = ActionState::Failure;
+            }
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,112 @@
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+use big_brain::prelude::*;
+use rand::Rng;
+
+use crate::{enemy::navigation::NavAgent, player::PlayerShip};
+
+pub struct WandererPlugin;
+
+impl Plugin for WandererPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, (wander_scorer_system, wander_action_system))
+            .register_type::<Wanderer>();
+    }
+}
+
+/// Drifts between random points around where the enemy was spawned
+#[derive(Component, Reflect, Default)]
+#[reflect(Component)]
+pub struct Wanderer {
+    /// How far from `home` the enemy goes
+    pub leash: f32,
+    pub speed: f32,
+    pub home: Vec3,
+    target: Option<Vec3>,
+    /// When to pick somewhere else, in case the target can't be reached
+    give_up_at: f64,
+}
+
+impl Wanderer {
+    pub fn new(leash: f32, speed: f32, home: Vec3) -> Self {
+        Self { leash, speed, home, ..default() }
+    }
+}
+
+#[derive(Component, ScorerBuilder, Debug, Clone)]
+pub struct Wander;
+
+#[derive(Component, ActionBuilder, Debug, Clone)]
+pub struct Wandering;
+
+/// Wandering is what enemies do when there is nothing better to do
+const WANDER_SCORE: f32 = 0.1;
+
+const WANDER_ARRIVAL_DISTANCE: f32 = 1.;
+
+/// Seconds to spend getting to a point before picking another one
+const WANDER_TIMEOUT: f64 = 8.;
+
+pub fn wander_scorer_system(
+    wanderers: Query<(), With<Wanderer>>,
+    mut query: Query<(&Actor, &mut Score), With<Wander>>,
+) {
+    for (Actor(actor), mut score) in &mut query {
+        if wanderers.contains(*actor) {
+            score.set(WANDER_SCORE);
+        }
+    }
+}
+
+pub fn wander_action_system(
+    time: Res<Time>,
+    mut enemies: Query<
+        (&Transform, &mut Wanderer, &mut NavAgent, &mut ExternalImpulse),
+        Without<PlayerShip>,
+    >,
+    mut query: Query<(&Actor, &mut ActionState, &ActionSpan), With<Wandering>>,
+) {
+    let now = time.elapsed_seconds_f64();
+
+    for (Actor(actor), mut state, span) in &mut query {
+        let _guard = span.span().enter();
+
+        let Ok((transform, mut wanderer, mut agent, mut impulse)) = enemies.get_mut(*actor) else {
+            continue;
+        };
+
+        match *state {
+            ActionState::Requested => {
+                debug!("start wandering");
+                wanderer.target = None;
+                *state = ActionState::Executing;
+            }
+            ActionState::Executing => {
+                let arrived = wanderer.target.map_or(true, |target| {
+                    target.distance(transform.translation) < WANDER_ARRIVAL_DISTANCE
+                });
+
+                if arrived || now > wanderer.give_up_at {
+                    let mut rng = rand::thread_rng();
+                    let angle = rng.gen_range(0. ..std::f32::consts::TAU);
+                    let distance = wanderer.leash * rng.gen::<f32>().sqrt();
+
+                    wanderer.target =
+                        Some(wanderer.home + Vec2::from_angle(angle).extend(0.) * distance);
+                    wanderer.give_up_at = now + WANDER_TIMEOUT;
+                }
+
+                agent.destination = wanderer.target;
+                let direction = agent.steer(transform.translation);
+
+                impulse.impulse += direction * wanderer.speed * time.delta_seconds();
+            }
+            ActionState::Cancelled => {
+                debug!("wandering cancelled");
+                agent.destination = None;
+                *state = ActionState::Failure;
+            }
+            _ => {}
+        }
+    }
+}
@@ -1,12 +1,16 @@
 use bevy::prelude::*;
 use bevy_rapier3d::prelude::*;
+use serde::{Deserialize, Serialize};
 
+use self::archetype::{EnemyArchetype, EnemyArchetypeLoader, WeaponRange};
 use crate::{damageable::Damageable, player::PlayerShip, team::Team};
 
-pub mod amoeba;
+pub mod archetype;
+pub mod navigation;
 pub mod behaviour {
     pub mod chase;
-    // pub mod wander;
+    pub mod patrol;
+    pub mod wander;
 }
 
 pub struct EnemyPlugin;
@@ -14,6 +18,23 @@ pub struct EnemyPlugin;
 #[derive(Component)]
 pub struct Enemy;
 
+/// The kinds of enemy a map can place a spawn token for
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub enum EnemyKind {
+    Amoeba,
+    /// The archetype in `assets/enemies/{name}.enemy.ron`
+    Archetype(String),
+}
+
+impl EnemyKind {
+    pub fn archetype(&self) -> &str {
+        match self {
+            EnemyKind::Amoeba => "amoeba",
+            EnemyKind::Archetype(name) => name,
+        }
+    }
+}
+
 #[derive(Component, Reflect, Default)]
 #[reflect(Component)]
 pub struct ContactDamage {
@@ -22,10 +43,19 @@ pub struct ContactDamage {
 
 impl Plugin for EnemyPlugin {
     fn build(&self, app: &mut App) {
-        app.add_system(amoeba::spawn_amoeba)
+        app.add_asset::<EnemyArchetype>()
+            .add_asset_loader(EnemyArchetypeLoader)
+            .add_systems(
+                Update,
+                (archetype::load_archetypes, archetype::spawn_enemies, archetype::aim_weapons),
+            )
             .add_system(contact_damage_system)
+            .add_plugins(navigation::NavigationPlugin)
             .add_plugin(behaviour::chase::PlayerChaserPlugin)
-            .register_type::<ContactDamage>();
+            .add_plugins(behaviour::patrol::PatrollerPlugin)
+            .add_plugins(behaviour::wander::WandererPlugin)
+            .register_type::<ContactDamage>()
+            .register_type::<WeaponRange>();
     }
 }
 
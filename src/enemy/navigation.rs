@@ -0,0 +1,568 @@
+//! Finding paths around walls. Walls block the cells of a grid that enemies can't fit in, and
+//! paths through the open cells are found with A*.
+//!
+//! The grid is built from the walls' lines and surfaces, the same way their colliders are, so it
+//! covers the whole map, including walls whose colliders are disabled while they are far away.
+//! Only the cells around a wall that changed are redone.
+
+use std::{cmp::Reverse, collections::BinaryHeap};
+
+use bevy::{prelude::*, utils::HashMap};
+
+use crate::{
+    editor::{
+        mesh::{wall_lines, WallMesh},
+        streaming::wall_bounds,
+    },
+    surface::Surface,
+};
+
+pub struct NavigationPlugin;
+
+impl Plugin for NavigationPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<NavigationSettings>()
+            .register_type::<NavigationSettings>()
+            .init_resource::<NavGrid>()
+            .add_systems(Update, (update_nav_grid, plan_paths).chain());
+    }
+}
+
+#[derive(Resource, Reflect)]
+#[reflect(Resource)]
+pub struct NavigationSettings {
+    /// Width of a grid cell. Changing it rebuilds the whole grid.
+    pub cell_size: f32,
+    /// How far enemies keep from walls. Cells closer to a wall than this are blocked.
+    pub clearance: f32,
+    /// Seconds to wait after a wall changes before redoing its cells, so dragging walls around in
+    /// the editor doesn't redo them every frame
+    pub rebuild_delay: f64,
+    /// Seconds between an agent's paths, as long as its destination stays in the same cell
+    pub replan_interval: f64,
+    /// How many cells a search looks at before giving up, which is what ends searches for
+    /// places that can't be reached
+    pub max_expansions: usize,
+}
+
+impl Default for NavigationSettings {
+    fn default() -> Self {
+        Self {
+            cell_size: 1.,
+            clearance: 0.5,
+            rebuild_delay: 0.25,
+            replan_interval: 0.5,
+            max_expansions: 4096,
+        }
+    }
+}
+
+/// The cells of the play plane that walls block. Every other cell is open, however far from the
+/// map it is.
+#[derive(Resource)]
+pub struct NavGrid {
+    cell_size: f32,
+    /// How many walls block each blocked cell
+    blocked: HashMap<IVec2, u32>,
+    /// The cells each wall blocks, so they can be opened again when it changes
+    walls: HashMap<Entity, Vec<IVec2>>,
+    /// Walls that changed, and when they last did
+    pending: HashMap<Entity, f64>,
+    /// Goes up whenever cells change, so agents know to plan again
+    generation: u32,
+}
+
+impl Default for NavGrid {
+    fn default() -> Self {
+        Self::new(NavigationSettings::default().cell_size)
+    }
+}
+
+const STRAIGHT_COST: u32 = 10;
+const DIAGONAL_COST: u32 = 14;
+
+const NEIGHBOURS: [IVec2; 8] = [
+    IVec2::new(1, 0),
+    IVec2::new(-1, 0),
+    IVec2::new(0, 1),
+    IVec2::new(0, -1),
+    IVec2::new(1, 1),
+    IVec2::new(1, -1),
+    IVec2::new(-1, 1),
+    IVec2::new(-1, -1),
+];
+
+impl NavGrid {
+    pub fn new(cell_size: f32) -> Self {
+        Self {
+            cell_size,
+            blocked: HashMap::new(),
+            walls: HashMap::new(),
+            pending: HashMap::new(),
+            generation: 0,
+        }
+    }
+
+    fn cell(&self, point: Vec2) -> IVec2 {
+        (point / self.cell_size).floor().as_ivec2()
+    }
+
+    fn center(&self, cell: IVec2) -> Vec2 {
+        (cell.as_vec2() + 0.5) * self.cell_size
+    }
+
+    fn is_open(&self, cell: IVec2) -> bool {
+        !self.blocked.contains_key(&cell)
+    }
+
+    fn block_cells(&mut self, wall: Entity, cells: Vec<IVec2>) {
+        for cell in &cells {
+            *self.blocked.entry(*cell).or_default() += 1;
+        }
+
+        self.walls.insert(wall, cells);
+    }
+
+    fn open_cells(&mut self, wall: Entity) {
+        for cell in self.walls.remove(&wall).unwrap_or_default() {
+            if let Some(count) = self.blocked.get_mut(&cell) {
+                *count -= 1;
+                if *count == 0 {
+                    self.blocked.remove(&cell);
+                }
+            }
+        }
+    }
+
+    /// Blocks the cells closer to the wall than `clearance`, going by the collider it gets
+    fn add_wall(
+        &mut self,
+        wall: Entity,
+        lines: &[(Vec3, Vec3)],
+        transform: &Transform,
+        surface: &Surface,
+        clearance: f32,
+    ) {
+        if !surface.collidable {
+            return;
+        }
+
+        let Some(collider) = surface.collider(lines, transform) else {
+            return;
+        };
+
+        // Cells count as blocked when any part of them is too close, not just their middle
+        let reach = clearance + self.cell_size * std::f32::consts::FRAC_1_SQRT_2;
+        let bounds = wall_bounds(lines, transform).inset(reach + surface.thickness);
+        let (min, max) = (self.cell(bounds.min), self.cell(bounds.max));
+
+        // The collider is relative to the wall, apart from its scale
+        let inverse_rotation = transform.rotation.inverse();
+
+        let mut cells = vec![];
+        for y in min.y..=max.y {
+            for x in min.x..=max.x {
+                let cell = IVec2::new(x, y);
+                let point =
+                    inverse_rotation * (self.center(cell).extend(0.) - transform.translation);
+
+                if collider.distance_to_local_point(point, true) < reach {
+                    cells.push(cell);
+                }
+            }
+        }
+
+        self.block_cells(wall, cells);
+    }
+
+    /// Whether a straight line between the points only goes through open cells. Lines going
+    /// exactly through the corner of a cell need both cells beside the corner to be open.
+    fn is_clear(&self, from: Vec2, to: Vec2) -> bool {
+        let (from, to) = (from / self.cell_size, to / self.cell_size);
+        let mut cell = from.floor().as_ivec2();
+        let end = to.floor().as_ivec2();
+
+        // How each axis steps, how far along the line it first crosses into the next cell, and
+        // how far apart the crossings are after that
+        let axis = |position: f32, cell: i32, direction: f32| {
+            // Lines that don't move along an axis never cross into another cell along it
+            if direction == 0. {
+                return (0, f32::INFINITY, f32::INFINITY);
+            }
+
+            let delta = 1. / direction.abs();
+            match direction > 0. {
+                true => (1, (cell as f32 + 1. - position) * delta, delta),
+                false => (-1, (position - cell as f32) * delta, delta),
+            }
+        };
+
+        let direction = to - from;
+        let (step_x, t_max_x, delta_x) = axis(from.x, cell.x, direction.x);
+        let (step_y, t_max_y, delta_y) = axis(from.y, cell.y, direction.y);
+        let step = IVec2::new(step_x, step_y);
+        let delta = Vec2::new(delta_x, delta_y);
+        let mut t_max = Vec2::new(t_max_x, t_max_y);
+
+        if !self.is_open(cell) {
+            return false;
+        }
+
+        while cell != end {
+            if t_max.x > 1. && t_max.y > 1. {
+                break;
+            }
+
+            if t_max.x < t_max.y {
+                cell.x += step.x;
+                t_max.x += delta.x;
+            } else if t_max.y < t_max.x {
+                cell.y += step.y;
+                t_max.y += delta.y;
+            } else {
+                if !self.is_open(cell + IVec2::new(step.x, 0))
+                    || !self.is_open(cell + IVec2::new(0, step.y))
+                {
+                    return false;
+                }
+
+                cell += step;
+                t_max += delta;
+            }
+
+            if !self.is_open(cell) {
+                return false;
+            }
+        }
+
+        true
+    }
+
+    /// The closest open cell, for points that ended up right next to a wall
+    fn nearest_open(&self, cell: IVec2) -> Option<IVec2> {
+        (0..4).find_map(|radius| {
+            (-radius..=radius)
+                .flat_map(|y| (-radius..=radius).map(move |x| cell + IVec2::new(x, y)))
+                .find(|cell| self.is_open(*cell))
+        })
+    }
+
+    /// Points to go through to get from one point to another, not including `from`. `None` if
+    /// there is no way through within `max_expansions` cells.
+    pub fn find_path(&self, from: Vec2, to: Vec2, max_expansions: usize) -> Option<Vec<Vec2>> {
+        if self.is_clear(from, to) {
+            return Some(vec![to]);
+        }
+
+        let start = self.nearest_open(self.cell(from))?;
+        let goal = self.nearest_open(self.cell(to))?;
+
+        let estimate = |cell: IVec2| {
+            let distance = (goal - cell).abs();
+            let (long, short) = (distance.max_element() as u32, distance.min_element() as u32);
+            STRAIGHT_COST * (long - short) + DIAGONAL_COST * short
+        };
+
+        let mut costs = HashMap::from([(start, 0)]);
+        let mut came_from = HashMap::new();
+        let mut queue = BinaryHeap::from([Reverse((estimate(start), start.to_array()))]);
+        let mut expansions = 0;
+
+        while let Some(Reverse((_, cell))) = queue.pop() {
+            let cell = IVec2::from_array(cell);
+            if cell == goal {
+                break;
+            }
+
+            expansions += 1;
+            if expansions > max_expansions {
+                return None;
+            }
+
+            for offset in NEIGHBOURS {
+                let next = cell + offset;
+                if !self.is_open(next) {
+                    continue;
+                }
+
+                // Diagonal steps can't cut the corners of walls
+                let diagonal = offset.x != 0 && offset.y != 0;
+                if diagonal
+                    && !(self.is_open(cell + IVec2::new(offset.x, 0))
+                        && self.is_open(cell + IVec2::new(0, offset.y)))
+                {
+                    continue;
+                }
+
+                let step = if diagonal {
+                    DIAGONAL_COST
+                } else {
+                    STRAIGHT_COST
+                };
+                let cost = costs[&cell] + step;
+                if costs.get(&next).map_or(true, |known| cost < *known) {
+                    costs.insert(next, cost);
+                    came_from.insert(next, cell);
+                    queue.push(Reverse((cost + estimate(next), next.to_array())));
+                }
+            }
+        }
+
+        if !costs.contains_key(&goal) {
+            return None;
+        }
+
+        let mut cells = vec![goal];
+        while let Some(previous) = cells.last().and_then(|cell| came_from.get(cell)) {
+            cells.push(*previous);
+        }
+        cells.reverse();
+
+        let mut points: Vec<_> = cells
+            .into_iter()
+            .skip(1)
+            .map(|cell| self.center(cell))
+            .collect();
+        points.pop();
+        points.push(to);
+
+        // Skip the points that can be cut straight past, so agents don't zigzag from cell to cell
+        let mut path = vec![];
+        let mut position = from;
+        let mut i = 0;
+        while i < points.len() {
+            let furthest = (i..points.len())
+                .rev()
+                .find(|j| self.is_clear(position, points[*j]))
+                .unwrap_or(i);
+
+            position = points[furthest];
+            path.push(position);
+            i = furthest + 1;
+        }
+
+        Some(path)
+    }
+}
+
+/// Moves an enemy towards a destination, around walls. Whatever moves the enemy sets the
+/// destination and pushes it in the direction of [`NavAgent::steer`].
+#[derive(Component, Default)]
+pub struct NavAgent {
+    pub destination: Option<Vec3>,
+    /// What's left of the path to the destination
+    path: Vec<Vec2>,
+    planned_for: Option<Vec2>,
+    planned_at: f64,
+    generation: u32,
+}
+
+impl NavAgent {
+    /// Which way to go to follow the path. Goes straight towards the destination until a path
+    /// has been planned, or if there is none.
+    pub fn steer(&self, position: Vec3) -> Vec3 {
+        let Some(destination) = self.destination else {
+            return Vec3::ZERO;
+        };
+
+        let target = self
+            .path
+            .first()
+            .map_or(destination, |point| point.extend(destination.z));
+
+        (target - position)
+            .truncate()
+            .normalize_or_zero()
+            .extend(0.)
+    }
+}
+
+pub fn update_nav_grid(
+    time: Res<Time>,
+    settings: Res<NavigationSettings>,
+    mut grid: ResMut<NavGrid>,
+    changed: Query<
+        Entity,
+        (With<WallMesh>, Or<(Changed<Transform>, Changed<Surface>, Changed<Handle<Mesh>>)>),
+    >,
+    all_walls: Query<Entity, With<WallMesh>>,
+    mut removed: RemovedComponents<WallMesh>,
+    walls: Query<(&Transform, &Handle<Mesh>, &Surface), With<WallMesh>>,
+    meshes: Res<Assets<Mesh>>,
+) {
+    let now = time.elapsed_seconds_f64();
+
+    // A new cell size or clearance changes every cell, so everything is redone right away
+    if settings.is_changed() {
+        let generation = grid.generation;
+        *grid = NavGrid::new(settings.cell_size);
+        grid.generation = generation;
+        grid.pending = all_walls
+            .iter()
+            .map(|wall| (wall, f64::NEG_INFINITY))
+            .collect();
+    }
+
+    for wall in changed.iter().chain(removed.iter()) {
+        grid.pending.insert(wall, now);
+    }
+
+    let ready: Vec<_> = grid
+        .pending
+        .iter()
+        .filter(|(_, changed_at)| now - **changed_at >= settings.rebuild_delay)
+        .map(|(wall, _)| *wall)
+        .collect();
+
+    if ready.is_empty() {
+        return;
+    }
+
+    for wall in ready {
+        grid.pending.remove(&wall);
+        grid.open_cells(wall);
+
+        // Walls that are gone only leave their cells open
+        let Ok((transform, mesh, surface)) = walls.get(wall) else {
+            continue;
+        };
+        let Some(lines) = meshes.get(mesh).and_then(wall_lines) else {
+            continue;
+        };
+
+        grid.add_wall(wall, &lines, transform, surface, settings.clearance);
+    }
+
+    grid.generation += 1;
+}
+
+pub fn plan_paths(
+    time: Res<Time>,
+    settings: Res<NavigationSettings>,
+    grid: Res<NavGrid>,
+    mut agents: Query<(&Transform, &mut NavAgent)>,
+) {
+    let now = time.elapsed_seconds_f64();
+
+    for (transform, mut agent) in &mut agents {
+        let position = transform.translation.truncate();
+
+        let Some(destination) = agent.destination.map(|destination| destination.truncate()) else {
+            agent.path.clear();
+            agent.planned_for = None;
+            continue;
+        };
+
+        // Move on once a point is reached
+        let reach = grid.cell_size / 2.;
+        if agent.path.len() > 1 && agent.path[0].distance(position) < reach {
+            agent.path.remove(0);
+        }
+
+        let moved = agent
+            .planned_for
+            .map_or(true, |planned_for| planned_for.distance(destination) > grid.cell_size);
+        let stale = agent.generation != grid.generation
+            || now - agent.planned_at > settings.replan_interval;
+
+        if !moved && !stale {
+            continue;
+        }
+
+        agent.path = grid
+            .find_path(position, destination, settings.max_expansions)
+            .unwrap_or_else(|| vec![destination]);
+        agent.planned_for = Some(destination);
+        agent.planned_at = now;
+        agent.generation = grid.generation;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MAX_EXPANSIONS: usize = 4096;
+
+    fn grid(blocked: impl IntoIterator<Item = (i32, i32)>) -> NavGrid {
+        let mut grid = NavGrid::new(1.);
+        let cells = blocked.into_iter().map(IVec2::from).collect();
+        grid.block_cells(Entity::PLACEHOLDER, cells);
+        grid
+    }
+
+    /// Whether every segment of the path, starting at `from`, stays in open cells
+    fn stays_open(grid: &NavGrid, from: Vec2, path: &[Vec2]) -> bool {
+        std::iter::once(from)
+            .chain(path.iter().copied())
+            .zip(path.iter().copied())
+            .all(|(a, b)| grid.is_clear(a, b))
+    }
+
+    #[test]
+    fn goes_straight_when_clear() {
+        let grid = grid([(5, 5)]);
+        let (from, to) = (Vec2::new(0.5, 0.5), Vec2::new(3.5, 1.5));
+
+        assert_eq!(grid.find_path(from, to, MAX_EXPANSIONS), Some(vec![to]));
+    }
+
+    #[test]
+    fn routes_around_wall() {
+        let grid = grid((-3..=3).map(|y| (2, y)));
+        let (from, to) = (Vec2::new(0.5, 0.5), Vec2::new(4.5, 0.5));
+
+        let path = grid.find_path(from, to, MAX_EXPANSIONS).unwrap();
+
+        assert!(path.len() > 1);
+        assert_eq!(path.last(), Some(&to));
+        assert!(stays_open(&grid, from, &path));
+    }
+
+    #[test]
+    fn never_cuts_blocked_corner() {
+        let grid = grid([(1, 0), (0, 1)]);
+        let (from, to) = (Vec2::new(0.5, 0.5), Vec2::new(1.5, 1.5));
+        let corner = Vec2::ONE;
+
+        assert!(!grid.is_clear(from, to));
+
+        let path = grid.find_path(from, to, MAX_EXPANSIONS).unwrap();
+
+        assert!(stays_open(&grid, from, &path));
+        for (a, b) in std::iter::once(from)
+            .chain(path.iter().copied())
+            .zip(path.iter().copied())
+        {
+            let along = ((corner - a).dot(b - a) / (b - a).length_squared()).clamp(0., 1.);
+            assert!(corner.distance(a + (b - a) * along) > 1e-3);
+        }
+    }
+
+    #[test]
+    fn axis_aligned_lines_on_cell_edges() {
+        let open = grid([]);
+        let blocked = grid([(0, 2)]);
+
+        // Going from 0 to -0 along X doesn't move along it at all
+        for (from, to) in [
+            (Vec2::new(0., 0.5), Vec2::new(-0., 3.5)),
+            (Vec2::new(1., 0.5), Vec2::new(1., 3.5)),
+            (Vec2::new(0.5, 1.), Vec2::new(3.5, 1.)),
+        ] {
+            assert!(open.is_clear(from, to));
+            assert!(open.is_clear(to, from));
+        }
+
+        assert!(!blocked.is_clear(Vec2::new(0., 0.5), Vec2::new(-0., 3.5)));
+    }
+
+    #[test]
+    fn enclosed_goal_has_no_path() {
+        let ring = (-2..=2).flat_map(|i| [(i, -2), (i, 2), (-2, i), (2, i)]);
+        let grid = grid(ring);
+        let (from, to) = (Vec2::new(10.5, 0.5), Vec2::new(0.5, 0.5));
+
+        assert_eq!(grid.find_path(from, to, MAX_EXPANSIONS), None);
+    }
+}
@@ -63,6 +63,51 @@ impl From<LineList> for Mesh {
     }
 }
 
+/// Colors for a list of lines, either for all of them at once or for each line or vertex
+#[derive(Reflect, Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum ColorSpec {
+    Uniform(Color),
+    PerLine(Vec<Color>),
+    PerVertex(Vec<(Color, Color)>),
+}
+
+impl Default for ColorSpec {
+    fn default() -> Self {
+        ColorSpec::Uniform(Color::WHITE)
+    }
+}
+
+impl ColorSpec {
+    /// The color of every vertex, two per line. Lines without a color of their own are white.
+    pub fn vertex_colors(&self, line_count: usize) -> Vec<Color> {
+        let mut colors: Vec<_> = match self {
+            ColorSpec::Uniform(color) => vec![*color; line_count * 2],
+            ColorSpec::PerLine(colors) => colors.iter().flat_map(|c| [*c, *c]).collect(),
+            ColorSpec::PerVertex(colors) => colors.iter().flat_map(|(a, b)| [*a, *b]).collect(),
+        };
+
+        colors.resize(line_count * 2, Color::WHITE);
+        colors
+    }
+
+    /// The most compact spec for the given vertex colors, two per line
+    pub fn from_vertex_colors(colors: &[Color]) -> Self {
+        let pairs: Vec<_> = colors
+            .chunks_exact(2)
+            .map(|pair| (pair[0], pair[1]))
+            .collect();
+
+        match pairs.first() {
+            None => ColorSpec::default(),
+            Some(&(first, _)) if colors.iter().all(|c| *c == first) => ColorSpec::Uniform(first),
+            _ if pairs.iter().all(|(a, b)| a == b) => {
+                ColorSpec::PerLine(pairs.into_iter().map(|(a, _)| a).collect())
+            }
+            _ => ColorSpec::PerVertex(pairs),
+        }
+    }
+}
+
 /// A list of points that will have a line drawn between each consecutive points
 #[derive(Debug, Clone)]
 pub struct LineStrip {
@@ -22,9 +22,13 @@ use leafwing_input_manager::prelude::{InputManagerPlugin, ToggleActions};
 use crate::{
     bullet::BulletPlugin,
     damageable::despawn_if_dead,
-    editor::EditorPlugin,
+    editor::{
+        scene::{default_map_path, CurrentMap},
+        EditorPlugin,
+    },
     enemy::EnemyPlugin,
     line_material::LineMaterial,
+    pickup::PickupPlugin,
     player::{input::PlayerAction, systems::PlayerFollower, PlayerPlugin},
     utils::zlock::ZLockPlugin,
     weapon::WeaponPlugin,
@@ -32,14 +36,17 @@ use crate::{
 
 mod assets;
 mod bullet;
+mod cli;
 mod collision_groups;
 mod damageable;
 mod editor;
 mod egui_style;
 mod enemy;
 mod line_material;
+mod pickup;
 mod player;
 mod render_layers;
+mod surface;
 mod team;
 mod weapon;
 mod utils {
@@ -51,6 +58,18 @@ mod utils {
 const CAMERA_OFFSET: Vec3 = Vec3::new(0., -5., 50.);
 
 fn main() {
+    // Tools for working on levels, which don't need a window
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if let Some(status) = cli::run(&args) {
+        std::process::exit(status);
+    }
+
+    // Anything else is a map to open, like `maps/level.dxf`
+    let map = match args.first() {
+        Some(path) => path.strip_prefix("assets/").unwrap_or(path).to_owned(),
+        None => default_map_path(),
+    };
+
     App::new()
         .insert_resource(Msaa::Off)
         .add_plugins(
@@ -95,18 +114,23 @@ fn main() {
         .insert_resource(ToggleActions::<EditorAction>::DISABLED)
         .add_systems(Startup, setup_windows_cameras)
         .add_systems(Startup, disable_gravity)
-        .add_systems(Startup, load_scene)
+        .add_systems(Startup, load_scene(map))
         .add_systems(Update, egui_style::set_egui_style)
         // .add_startup_system(map::spawn_map)
         .add_systems(Update, cycle_msaa)
         .add_systems(Update, despawn_if_dead)
         .add_systems(Update, handle_window_focus_events)
-        .add_systems(Update, replace_standard_material)
+        // Animated models copy the line material they end up with, so it has to be there first
+        .add_systems(
+            Update,
+            replace_standard_material.before(assets::animation::start_model_animations),
+        )
         .add_plugins(AssetsPlugin)
         .add_plugins(PlayerPlugin)
         .add_plugins(WeaponPlugin)
         .add_plugins(BulletPlugin)
         .add_plugins(EnemyPlugin)
+        .add_plugins(PickupPlugin)
         .add_plugins(ZLockPlugin)
         .add_plugins(EditorPlugin)
         .run();
@@ -151,8 +175,10 @@ fn setup_windows_cameras(mut commands: Commands, mut windows: Query<Entity, With
     ));
 }
 
-fn load_scene(commands: Commands, meshes: ResMut<Assets<Mesh>>) {
-    editor::scene::load_scene("assets/maps/world.map.ron", commands, meshes);
+fn load_scene(path: String) -> impl FnMut(Commands, Res<AssetServer>) {
+    move |mut commands, asset_server| {
+        commands.insert_resource(CurrentMap::load(path.clone(), &asset_server));
+    }
 }
 
 fn cycle_msaa(input: Res<Input<KeyCode>>, mut msaa: ResMut<Msaa>) {
@@ -0,0 +1,91 @@
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    collision_groups, damageable::Damageable, line_material::LineList, player::PlayerShip,
+    utils::drawing::circle,
+};
+
+pub struct PickupPlugin;
+
+impl Plugin for PickupPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, spawn_pickup)
+            .add_systems(Update, collect_pickups)
+            .register_type::<Pickup>();
+    }
+}
+
+#[derive(Serialize, Deserialize, Reflect, Default, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PickupKind {
+    #[default]
+    Health,
+}
+
+#[derive(Component, Reflect, Default)]
+#[reflect(Component)]
+pub struct Pickup {
+    pub kind: PickupKind,
+    pub amount: f32,
+}
+
+fn spawn_pickup(
+    query: Query<(Entity, &Transform, &Pickup), Added<Pickup>>,
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+) {
+    for (entity, transform, pickup) in query.iter() {
+        let color = match pickup.kind {
+            PickupKind::Health => Color::rgb(0., 4., 1.),
+        };
+
+        commands.entity(entity).insert((
+            MaterialMeshBundle::<StandardMaterial> {
+                mesh: meshes.add(Mesh::from(LineList { lines: circle(0.4, 4), color })),
+                transform: *transform,
+                ..default()
+            },
+            Collider::ball(0.5),
+            Sensor,
+            CollisionGroups::new(collision_groups::PICKUP, collision_groups::PLAYER),
+        ));
+    }
+}
+
+fn collect_pickups(
+    context: Res<RapierContext>,
+    pickups: Query<(Entity, &Pickup)>,
+    mut players: Query<Option<&mut Damageable>, With<PlayerShip>>,
+    mut commands: Commands,
+) {
+    for (entity, pickup) in pickups.iter() {
+        for (collider1, collider2, intersecting) in context.intersections_with(entity) {
+            if !intersecting {
+                continue;
+            }
+
+            let other = if collider1 == entity {
+                collider2
+            } else {
+                collider1
+            };
+
+            let Ok(damageable) = players.get_mut(other) else {
+                continue;
+            };
+
+            match pickup.kind {
+                PickupKind::Health => {
+                    if let Some(mut damageable) = damageable {
+                        damageable.health =
+                            (damageable.health + pickup.amount).min(damageable.max_health);
+                    }
+                }
+            }
+
+            commands.entity(entity).despawn_recursive();
+            break;
+        }
+    }
+}
@@ -10,6 +10,10 @@ pub struct PlayerShip;
 #[derive(Component)]
 pub struct PlayerAimTarget;
 
+/// Where the map wants the player ship to begin
+#[derive(Component)]
+pub struct PlayerStart;
+
 #[derive(Component, Reflect, Default)]
 #[reflect(Component)]
 pub struct ShipEngine {
@@ -29,6 +33,7 @@ impl Plugin for PlayerPlugin {
             .add_systems(Update, systems::move_aim_target_gamepad)
             .add_systems(Update, systems::move_aim_target_mouse)
             .add_systems(Update, systems::aim_player_ship)
+            .add_systems(Update, systems::move_to_player_start)
             .register_type::<ShipEngine>();
     }
 }
@@ -1,46 +1,30 @@
-use std::f32::consts::PI;
-
 use bevy::prelude::*;
 use bevy_rapier3d::prelude::*;
 use leafwing_input_manager::prelude::*;
 
 use super::{input::PlayerAction, PlayerAimTarget, PlayerShip, ShipEngine};
 use crate::{
+    assets::animation::PlayModelAnimation,
     collision_groups,
     team::Team,
     utils::zlock::ZLocked,
     weapon::{Weapon, WeaponTrigger},
 };
 
-pub fn spawn_player(
-    mut commands: Commands,
-    asset_server: Res<AssetServer>,
-    mut animations: ResMut<Assets<AnimationClip>>,
-) {
+pub fn spawn_player(mut commands: Commands, asset_server: Res<AssetServer>) {
     let name = Name::new("Player ship");
 
-    let mesh_name = Name::new("Player ship model");
-    let mut animation = AnimationClip::default();
-    animation.add_curve_to_path(EntityPath { parts: vec![mesh_name.clone()] }, VariableCurve {
-        keyframe_timestamps: vec![0., 2., 4.],
-        keyframes: Keyframes::Rotation(vec![
-            Quat::from_rotation_x(0.),
-            Quat::from_rotation_x(PI * 1.),
-            Quat::from_rotation_x(PI * 2.),
-        ]),
-    });
-
-    let mut animation_player = AnimationPlayer::default();
-    animation_player.play(animations.add(animation)).repeat();
-
     let mesh = commands
         .spawn((
-            mesh_name,
+            Name::new("Player ship model"),
             MaterialMeshBundle::<StandardMaterial> {
-                mesh: asset_server.load("models/ship.mdl.ron"),
+                mesh: asset_server.load("models/ship.mdl.ron#mesh"),
                 ..default()
             },
-            animation_player,
+            PlayModelAnimation {
+                model: asset_server.load("models/ship.mdl.ron"),
+                animation: "spin".to_owned(),
+            },
         ))
         .id();
 
@@ -90,7 +74,7 @@ pub fn spawn_player(
     commands.spawn((
         Name::new("Player Aim Target"),
         MaterialMeshBundle::<StandardMaterial> {
-            mesh: asset_server.load("models/ship-target.mdl.ron"),
+            mesh: asset_server.load("models/ship-target.mdl.ron#mesh"),
             transform: Transform::from_translation(Vec3::X * 9999.),
             ..default()
         },
@@ -2,7 +2,7 @@ use bevy::prelude::*;
 use bevy_rapier3d::prelude::*;
 use leafwing_input_manager::prelude::*;
 
-use super::{input::PlayerAction, PlayerAimTarget, PlayerShip, ShipEngine};
+use super::{input::PlayerAction, PlayerAimTarget, PlayerShip, PlayerStart, ShipEngine};
 use crate::{utils::look_at_2d::LookAt2d, weapon::WeaponTrigger, PlayerWindow, CAMERA_OFFSET};
 
 pub fn move_player_ship(
@@ -107,3 +107,23 @@ pub fn aim_player_ship(
 
     player_ship.look_at_2d(player_aim_target.translation);
 }
+
+pub fn move_to_player_start(
+    starts: Query<&Transform, Added<PlayerStart>>,
+    mut player_ship: Query<&mut Transform, (With<PlayerShip>, Without<PlayerStart>)>,
+    mut placed: Local<bool>,
+) {
+    // Only the first start counts, reloading the map shouldn't yank the ship around
+    if *placed {
+        return;
+    }
+
+    let Some(start) = starts.iter().next() else {
+        return;
+    };
+
+    for mut transform in player_ship.iter_mut() {
+        transform.translation = start.translation;
+        *placed = true;
+    }
+}
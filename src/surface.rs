@@ -0,0 +1,137 @@
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::collision_groups;
+
+/// How far walls extend above and below the play plane
+const WALL_HALF_HEIGHT: f32 = 1.;
+
+#[derive(Reflect, Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum WallShape {
+    /// A wall with no thickness along every line
+    #[default]
+    Polyline,
+    /// A capsule around every line, `thickness` wide
+    ThickSegments,
+    /// One solid block covering all of the lines
+    ConvexHull,
+}
+
+/// How a wall treats the things that touch it
+#[derive(Component, Reflect, Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[reflect(Component)]
+#[serde(default)]
+pub struct Surface {
+    /// Walls that aren't collidable are purely decorative and get no collider at all
+    pub collidable: bool,
+    /// Damage dealt to the player for every frame spent touching the wall
+    pub contact_damage: f32,
+    pub reflects_bullets: bool,
+    pub bullets_pass: bool,
+    pub restitution: f32,
+    pub shape: WallShape,
+    pub thickness: f32,
+}
+
+impl Default for Surface {
+    fn default() -> Self {
+        Self {
+            collidable: true,
+            contact_damage: 0.,
+            reflects_bullets: false,
+            bullets_pass: false,
+            restitution: 0.,
+            shape: WallShape::Polyline,
+            thickness: 0.5,
+        }
+    }
+}
+
+impl Surface {
+    pub fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+
+    pub fn collision_groups(&self) -> CollisionGroups {
+        let filters = if self.bullets_pass {
+            collision_groups::ALL & !collision_groups::BULLET
+        } else {
+            collision_groups::ALL
+        };
+
+        CollisionGroups::new(collision_groups::WALL, filters)
+    }
+
+    /// Builds a collider for `lines` as they end up on the play plane once `transform` has been
+    /// applied, so walls that are tilted or scaled still block things where they appear to.
+    ///
+    /// The result is relative to the entity but already includes its scale, so it has to be
+    /// paired with `ColliderScale::Absolute(Vec3::ONE)`.
+    pub fn collider(&self, lines: &[(Vec3, Vec3)], transform: &Transform) -> Option<Collider> {
+        let inverse_rotation = transform.rotation.inverse();
+        let to_local = |point: Vec3| inverse_rotation * (point - transform.translation);
+        let flatten = |point: Vec3| Vec3::new(point.x, point.y, 0.);
+        let up = inverse_rotation * Vec3::Z * WALL_HALF_HEIGHT;
+
+        let segments: Vec<_> = lines
+            .iter()
+            .map(|(from, to)| {
+                let from = flatten(transform.transform_point(*from));
+                let to = flatten(transform.transform_point(*to));
+                (from, to)
+            })
+            .filter(|(from, to)| from.distance_squared(*to) > f32::EPSILON)
+            .map(|(from, to)| (to_local(from), to_local(to)))
+            .collect();
+
+        if segments.is_empty() {
+            return None;
+        }
+
+        match self.shape {
+            WallShape::Polyline => Some(polyline(&segments, up)),
+            WallShape::ThickSegments => Some(Collider::compound(
+                segments
+                    .into_iter()
+                    .map(|(from, to)| {
+                        (
+                            Vec3::ZERO,
+                            Quat::IDENTITY,
+                            Collider::capsule(from, to, self.thickness / 2.),
+                        )
+                    })
+                    .collect(),
+            )),
+            WallShape::ConvexHull => {
+                let points: Vec<_> = segments
+                    .iter()
+                    .flat_map(|(from, to)| [*from - up, *from + up, *to - up, *to + up])
+                    .collect();
+
+                // Lines that all lie along one line have no hull with any width
+                Collider::convex_hull(&points).or_else(|| {
+                    warn!("Wall lines have no convex hull, so it collides as a polyline instead");
+                    Some(polyline(&segments, up))
+                })
+            }
+        }
+    }
+}
+
+/// A wall with no thickness along every segment, reaching `up` above and below it
+fn polyline(segments: &[(Vec3, Vec3)], up: Vec3) -> Collider {
+    let mut vertices = vec![];
+    let mut indices = vec![];
+
+    for &(from, to) in segments {
+        let idx = vertices.len() as u32;
+
+        vertices.extend_from_slice(&[from - up, from + up, to - up, to + up]);
+
+        indices.push([idx, idx + 2, idx + 1]);
+        indices.push([idx + 1, idx + 2, idx + 3]);
+    }
+
+    Collider::trimesh(vertices, indices)
+}
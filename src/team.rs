@@ -1,6 +1,9 @@
 use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
 
-#[derive(Component, Reflect, Default, PartialEq, Eq, Clone, Copy, Debug)]
+#[derive(
+    Component, Reflect, Serialize, Deserialize, Default, PartialEq, Eq, Clone, Copy, Debug,
+)]
 #[reflect(Component)]
 pub enum Team {
     #[default]
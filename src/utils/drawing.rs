@@ -16,7 +16,7 @@ pub fn circle(radius: f32, segments: usize) -> Vec<(Vec3, Vec3)> {
     vertices
 }
 
-pub fn _arc(
+pub fn arc(
     radius: f32,
     start: f32,
     end: f32,
@@ -65,7 +65,7 @@ impl BulletBundle {
 }
 
 pub fn shoot(
-    mut query: Query<(&Transform, &mut Weapon, &mut WeaponTrigger, Option<&Team>)>,
+    mut query: Query<(&GlobalTransform, &mut Weapon, &mut WeaponTrigger, Option<&Team>)>,
     mut commands: Commands,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<LineMaterial>>,
@@ -78,6 +78,9 @@ pub fn shoot(
 
         trigger.0 = false;
 
+        // Weapons can be mounted on other entities, like the ones on enemies
+        let transform = transform.compute_transform();
+
         let Weapon {
             cooldown,
             ref mut next_shot,
@@ -117,7 +120,7 @@ pub fn shoot(
         // ));
 
         let bundle = BulletBundle::new(
-            *transform,
+            transform,
             Velocity {
                 linvel: direction * velocity,
                 angvel: Vec3::ZERO,